@@ -1,32 +1,114 @@
 use std::{collections::HashMap, path::PathBuf, str::FromStr};
 
 use crate::{
-  data_struct::{IError, Stack, Type, TypedByte},
-  macros::{compiler, ierror, replace_params, sanitize_param},
-  utils::{self, create_kind_byte, create_two_bits},
+  container::{constant_addresses, Container},
+  data_struct::{
+    param_width, IError, Record, Stack, Type, TypedByte, HEADER_SIZE,
+  },
+  diagnostic::{Diagnostic, DiagnosticCode, Origin, OriginKind},
+  expression::{Expr, ExprError, Operator, OPERATORS},
+  macros::{
+    compiler, diagnostic, ierror, replace_params, sanitize_param,
+  },
+  memory_map::{MemoryMap, Variable},
+  parser::{
+    parse, parse_line, tokenize, Declaration, Directive, Node, Param,
+    ParamKind, Span, Statement, SyntaxError, Token,
+  },
+  source_map::{SourceLocation, SourceMap},
+  utils::{
+    self, char_to_u32, crc32, create_two_bits, create_types_bytes,
+    u32_to_bytes,
+  },
   CompilerInfra, ROBSON_FOLDER, STDRB_FOLDER,
 };
 
+/// How many param lines follow each opcode
+pub const OPCODE_PARAMS: [u8; 24] = [
+  0, 3, 3, 1, 3, 1, 3, 0, 0, 1, 1, 0, 1, 1, 0, 1, 0, 1, 0, 3, 3, 3,
+  3, 3,
+];
+
+/// How many macro expressions can be nested
+const MACRO_DEPTH: usize = 9;
+
+/// The label holding the first cell of the vars, `:&`. A var
+/// address is this label plus its offset, patched once every file is
+/// compiled and the cells written by hand are known
+pub(crate) const DATA_LABEL: &str = "&";
+
+/// A `comeu` param made by the compiler, `value` may be an expression
+fn value_param(value: String, line: usize) -> Option<(Param, usize)> {
+  Some((
+    Param {
+      kind: ParamKind::Comeu,
+      value: Token {
+        text: value,
+        span: Span::default(),
+      },
+      convert: false,
+    },
+    line,
+  ))
+}
+
+/// A param using a label before it was defined, filled once the
+/// whole file is compiled
+struct Patch {
+  /// Where the param starts in the buffer
+  at: usize,
+  expr: Expr,
+  line: usize,
+}
+
+/// A `var` cell set once the program starts
+#[derive(Debug, Clone)]
+struct Init {
+  /// From the first cell of the vars
+  offset: usize,
+  /// Still an expression while it uses a var address
+  value: Expr,
+  r#type: Type,
+  path: String,
+  /// 1-based
+  line: usize,
+  source: String,
+  origins: Vec<Origin>,
+}
+
 pub struct Compiler {
   lines: Vec<String>,
-  opcode_params: [u8; 17],
+  nodes: Vec<Result<Node, SyntaxError>>,
+  opcode_params: [u8; 24],
   names: HashMap<String, usize>,
-  files: HashMap<String, (usize, usize)>,
+  constants: HashMap<String, Expr>,
+  patches: Vec<Patch>,
   pos: usize,
   debug: bool,
   current_command: usize,
   buffer: Vec<u8>,
   infra: Box<dyn CompilerInfra>,
-  is_preload: bool,
   compiled_stack: Vec<String>,
   last_opcode: u8,
   offset: usize,
   inner: usize,
   path: String,
-  is_static: bool,
   macro_params: Option<HashMap<String, String>>,
-  macro_current: Stack<10>,
-  macro_jump: Stack<10>,
+  macro_current: Stack,
+  macro_jump: Stack,
+  origins: Vec<Origin>,
+  recover: bool,
+  max_errors: usize,
+  diagnostics: Vec<Diagnostic>,
+  sources: Vec<(String, u32)>,
+  source_map: SourceMap,
+  /// The next free cell from `data_start`
+  next_address: usize,
+  data_start: Option<usize>,
+  memory_map: MemoryMap,
+  inits: Vec<Init>,
+  /// Where the target of the jump to the inits starts
+  init_jump: Option<usize>,
 }
 impl Compiler {
   pub fn new<'a>(
@@ -57,29 +139,112 @@ impl Compiler {
         .to_string();
     }
     let lines = infra.lines(&path)?;
-    Ok(Self {
+    Ok(Self::with_lines(path, lines, infra))
+  }
+
+  /// Compiles `source` as the file `name`, includes still go through
+  /// `infra`, see `MemoryCompilerInfra`
+  pub fn from_source(
+    name: String,
+    source: &str,
+    infra: Box<dyn CompilerInfra>,
+  ) -> Self {
+    let lines = source.lines().map(str::to_owned).collect();
+    Self::with_lines(name, lines, infra)
+  }
+
+  fn with_lines(
+    path: String,
+    lines: Vec<String>,
+    infra: Box<dyn CompilerInfra>,
+  ) -> Self {
+    let sources =
+      vec![(path.clone(), crc32(lines.join("\n").as_bytes()))];
+    Self {
+      nodes: parse(&lines),
       buffer: Vec::new(),
       debug: false,
       infra,
       last_opcode: 0,
       offset: 0,
-      files: HashMap::new(),
-      is_preload: false,
+      patches: Vec::new(),
       lines,
       current_command: 0,
       names: HashMap::new(),
+      constants: HashMap::new(),
       compiled_stack: Vec::new(),
-      macro_jump: Stack::new(),
-      macro_current: Stack::new(),
-      is_static: true,
-      opcode_params: [
-        0, 3, 3, 1, 3, 1, 3, 0, 0, 1, 1, 0, 1, 1, 0, 1, 0,
-      ],
+      macro_jump: Stack::new(MACRO_DEPTH),
+      macro_current: Stack::new(MACRO_DEPTH),
+      opcode_params: OPCODE_PARAMS,
       pos: 0,
       path,
       inner: 0,
       macro_params: None,
-    })
+      origins: Vec::new(),
+      recover: false,
+      max_errors: 100,
+      diagnostics: Vec::new(),
+      sources,
+      source_map: SourceMap::default(),
+      next_address: 0,
+      data_start: None,
+      memory_map: MemoryMap::default(),
+      inits: Vec::new(),
+      init_jump: None,
+    }
+  }
+
+  pub fn path(&self) -> &str {
+    &self.path
+  }
+
+  /// Builds a diagnostic for the 0-based `line` of this file
+  pub fn diagnostic(
+    &self,
+    code: DiagnosticCode,
+    line: usize,
+    token: &str,
+    message: String,
+  ) -> Diagnostic {
+    let line = line.min(self.lines.len().max(1) - 1);
+    let source = self.lines.get(line).map_or("", |a| a.as_str());
+    Diagnostic::error(
+      code,
+      message,
+      &self.path,
+      line + 1,
+      source,
+      token,
+    )
+    .with_chain(self.origins.clone())
+  }
+
+  /// Attaches a location to errors that don't carry one yet
+  fn locate(
+    &self,
+    err: IError,
+    code: DiagnosticCode,
+    line: usize,
+    token: &str,
+  ) -> IError {
+    if err.diagnostics.is_empty() {
+      self.diagnostic(code, line, token, err.error).into()
+    } else {
+      err
+    }
+  }
+
+  pub fn origin(&self, kind: OriginKind, line: usize) -> Vec<Origin> {
+    let mut origins = self.origins.clone();
+    origins.push(Origin {
+      kind,
+      path: self.path.clone(),
+      line: line + 1,
+    });
+    origins
+  }
+  pub fn set_origins(&mut self, origins: Vec<Origin>) {
+    self.origins = origins;
   }
 
   pub fn get_file_params(&self) -> Result<Vec<u32>, IError> {
@@ -101,28 +266,30 @@ impl Compiler {
         })
         .collect();
       if is_error {
-        return ierror!(
-          "Malformated param requirement at '{}' in '{}'",
-          self.lines[0],
-          self.path
+        return diagnostic!(
+          self,
+          MalformedRequirement,
+          0,
+          "",
+          "Malformated param requirement"
         );
       }
       Ok(splited)
     } else {
-      ierror!("The file '{}' is empty", self.path)
+      diagnostic!(
+        self,
+        MalformedRequirement,
+        0,
+        "",
+        "The file '{}' is empty",
+        self.path
+      )
     }
   }
 
   pub fn inner_in(&mut self, current: usize) {
     self.inner = current + 1;
   }
-  pub fn set_files(
-    &mut self,
-    new_compiled_files: HashMap<String, (usize, usize)>,
-  ) {
-    self.files = new_compiled_files;
-  }
-
   pub fn set_macro_params(
     &mut self,
     params: HashMap<String, String>,
@@ -145,622 +312,1075 @@ impl Compiler {
   pub fn set_offset(&mut self, offset: usize) {
     self.offset = offset;
   }
-  pub fn set_preload(&mut self, new_preload: bool) {
-    self.is_preload = new_preload;
+  /// The first memory cell given to a `var`, the ones before it are
+  /// left to hand picked addresses. By default it is the cell after
+  /// the last one the program writes at a constant address, a string
+  /// `input` writing up to its limit
+  pub fn set_data_start(&mut self, address: usize) {
+    self.data_start = Some(address);
+  }
+  pub fn set_recover(&mut self, recover: bool) {
+    self.recover = recover;
+  }
+  pub fn set_max_errors(&mut self, max_errors: usize) {
+    self.max_errors = max_errors;
+  }
+  pub fn diagnostics(&self) -> &[Diagnostic] {
+    &self.diagnostics
   }
-  pub fn compile(&mut self) -> Result<Vec<u8>, IError> {
-    self.start_command_alias()?;
 
-    if self.macro_params.is_some() {
+  /// Records the error and moves to the next line that starts a
+  /// statement, failing if recovery is off or the limit was reached
+  fn recover_from(&mut self, err: IError) -> Result<(), IError> {
+    self.record(err)?;
+
+    self.last_opcode = 0;
+    self.pos += 1;
+    while !self.verify_index_overflow(self.pos)
+      && !self.starts_statement(self.pos)
+    {
       self.pos += 1;
     }
+    Ok(())
+  }
 
-    loop {
-      if self.verify_index_overflow(self.pos) {
+  fn record(&mut self, err: IError) -> Result<(), IError> {
+    if !self.recover || err.diagnostics.is_empty() {
+      return Err(err);
+    }
+    for diagnostic in err.diagnostics {
+      if self.diagnostics.len() >= self.max_errors {
         break;
       }
-      let mut string = self.lines[self.pos].clone();
-
-      string = Self::remove_comments(&string).trim().to_owned();
+      if !self.diagnostics.contains(&diagnostic) {
+        self.diagnostics.push(diagnostic);
+      }
+    }
+    if self.diagnostics.len() >= self.max_errors {
+      return Err(IError::from_diagnostics(self.diagnostics.clone()));
+    }
+    Ok(())
+  }
 
-      // skip aliases
-      if string.ends_with(':') {
-        self.pos += 1;
-        continue;
+  /// Takes the source hashes and locations of an included file
+  fn inherit(&mut self, compiler: &mut Compiler) {
+    for source in &compiler.sources {
+      if !self.sources.contains(source) {
+        self.sources.push(source.clone());
       }
+    }
+    self
+      .source_map
+      .locations
+      .append(&mut compiler.source_map.locations);
+    self
+      .memory_map
+      .variables
+      .append(&mut compiler.memory_map.variables);
+    self.inits.append(&mut compiler.inits);
+    self.next_address = compiler.next_address;
+  }
 
-      // //skip spaces
-      if string.is_empty() {
-        self.pos += 1;
-        continue;
+  fn map_command(&mut self, line: usize) {
+    self.source_map.locations.push(SourceLocation {
+      path: self.path.clone(),
+      line: line + 1,
+      origins: self.origins.clone(),
+    });
+  }
+
+  pub fn source_map(&self) -> &SourceMap {
+    &self.source_map
+  }
+
+  /// The `var` addresses of every compiled file
+  pub fn memory_map(&self) -> &MemoryMap {
+    &self.memory_map
+  }
+
+  /// The labels of this file and their command index, in order
+  pub fn symbols(&self) -> Vec<(String, u32)> {
+    let mut symbols = self
+      .names
+      .iter()
+      .map(|(name, address)| (name.clone(), *address as u32))
+      .collect::<Vec<(String, u32)>>();
+    symbols.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(&b.0)));
+    symbols
+  }
+
+  /// Wraps a buffer returned by `compile` in a versioned container
+  /// carrying the labels and hashes of every compiled file
+  pub fn write_container(&self, code: &[u8]) -> Vec<u8> {
+    let mut container = Container::new(self.path.clone(), code);
+    container.symbols = self.symbols();
+    container.sources = self.sources.clone();
+    let debug = self.source_map.to_bytes();
+    container.debug = Some(&debug);
+    container.to_bytes()
+  }
+
+  /// Compiles every line once, the labels used before they are
+  /// defined are patched at the end
+  pub fn compile(&mut self) -> Result<Vec<u8>, IError> {
+    let is_root = self.inner == 0 && self.macro_params.is_none();
+    if is_root && self.declares_vars(&mut vec![self.path.clone()]) {
+      self.init_jump = Some(self.buffer.len() + HEADER_SIZE);
+      self.push_jump(0)?;
+    }
+    if self.macro_params.is_some() {
+      self.pos += 1;
+    }
+
+    while let Err(err) = self.compile_lines() {
+      self.recover_from(err)?;
+    }
+    self.backpatch()?;
+    if is_root {
+      self.place_vars()?;
+    }
+
+    if !self.diagnostics.is_empty() {
+      return Err(IError::from_diagnostics(self.diagnostics.clone()));
+    }
+    Ok(self.buffer.clone())
+  }
+
+  /// Whether this file or a file it pulls in declares a `var`, read
+  /// before compiling so the program can start with a jump to the
+  /// inits
+  fn declares_vars(&mut self, seen: &mut Vec<String>) -> bool {
+    let mut paths = Vec::new();
+    for node in self.nodes.iter().flatten() {
+      match &node.statement {
+        Statement::Var { .. } => return true,
+        Statement::Placeholder if node.tokens[0].text == "var" => {
+          return true
+        }
+        Statement::Include(path) | Statement::Macro(path) => {
+          paths.push(path.clone())
+        }
+        _ => {}
+      }
+    }
+    paths.into_iter().any(|path| {
+      if seen.contains(&path) {
+        return false;
       }
+      seen.push(path.clone());
+      Compiler::new(path, self.infra.clone_self())
+        .is_ok_and(|mut a| a.declares_vars(seen))
+    })
+  }
 
-      replace_params!(self, string);
+  /// Once every file is compiled, puts the vars after the cells
+  /// written by hand and sets them after the end of the program, where
+  /// the first command jumps. They jump back to the second one
+  fn place_vars(&mut self) -> Result<(), IError> {
+    let data_start = match self.data_start {
+      Some(address) => address,
+      None => {
+        let patched =
+          self.patches.iter().map(|a| a.at).collect::<Vec<usize>>();
+        constant_addresses(&self.buffer)
+          .into_iter()
+          .filter(|(at, _)| !patched.contains(at))
+          .map(|(_, cell)| cell as usize + 1)
+          .max()
+          .unwrap_or(0)
+      }
+    };
+    self.data_start = Some(data_start);
+    self.backpatch()?;
+    for variable in &mut self.memory_map.variables {
+      variable.address += data_start;
+    }
 
-      if string == "SEMPRE#ROBSON" {
-        self.is_static = false;
-        if self.macro_jump.sx != 0 {
-          // if let Some(top) = self.macro_jump.top() {
-          self.pos = self.macro_jump.top().into();
-          continue;
-        } else {
-          self.pos += 1;
+    let inits = std::mem::take(&mut self.inits);
+    let Some(init_jump) = self.init_jump else {
+      if inits.is_empty() {
+        return Ok(());
+      }
+      return ierror!(
+        "Cant set the vars declared by lines made of macro params"
+      );
+    };
+    let first = self.current_command + self.offset;
+    let target = match inits.len() {
+      0 => 1 + self.offset,
+      count => {
+        // the program ends before the inits
+        self.push_jump(first + 2 * count + 2)?;
+        first + 1
+      }
+    };
+    for init in &inits {
+      let mut value = match init.value.eval(&|name| self.label(name))
+      {
+        Ok(value) => value,
+        Err(err) => {
+          let diagnostic = Diagnostic::error(
+            DiagnosticCode::InvalidExpression,
+            err.to_string(),
+            &init.path,
+            init.line,
+            &init.source,
+            "",
+          )
+          .with_chain(init.origins.clone());
+          self.record(diagnostic.into())?;
           continue;
         }
+      };
+      value.convert(init.r#type);
+      let address = (data_start + init.offset).to_string();
+      self.push_command(
+        3,
+        [value_param(value.to_string(), 0), None, None],
+      )?;
+      self.push_command(10, [value_param(address, 0), None, None])?;
+      for _ in 0..2 {
+        self.source_map.locations.push(SourceLocation {
+          path: init.path.clone(),
+          line: init.line,
+          origins: init.origins.clone(),
+        });
       }
-      if string == "PARE#ROBSON" {
-        self.macro_current.pop();
-        self.pos += 1;
-        continue;
+    }
+    if !inits.is_empty() {
+      self.push_jump(1 + self.offset)?;
+    }
+    self.buffer[init_jump..init_jump + 4]
+      .copy_from_slice(&u32_to_bytes(target as u32));
+    Ok(())
+  }
+
+  /// A jump made by the compiler, located at the first line
+  fn push_jump(&mut self, target: usize) -> Result<(), IError> {
+    self.push_command(
+      9,
+      [value_param(target.to_string(), 0), None, None],
+    )?;
+    self.map_command(0);
+    Ok(())
+  }
+
+  fn compile_lines(&mut self) -> Result<(), IError> {
+    loop {
+      if self.verify_index_overflow(self.pos) {
+        break;
+      }
+      let mut node = self.node(self.pos)?;
+      if node.statement == Statement::Placeholder {
+        let mut string = node.text();
+        replace_params!(self, string);
+        node = self.reparse(&string)?;
       }
 
-      //INCLUDE LOGIC
-      if string.starts_with("robsons") {
-        let splited: Vec<&str> = string.split(' ').collect();
-        if splited.len() != 2 {
-          return Err(IError::message("Malformated robsons"));
+      match node.statement.clone() {
+        Statement::Empty => {
+          self.pos += 1;
         }
-        let file_path = splited[1];
-        let mut inner_spaces = String::from("");
-        if self.inner > 0 {
-          if self.inner > 1 {
-            for _i in 0..self.inner {
-              inner_spaces.push_str("  ");
-            }
+        Statement::Label(value) => {
+          self.define_label(value)?;
+          self.pos += 1;
+        }
+        Statement::Const { name, value } => {
+          self.define_const(name, &value)?;
+          self.pos += 1;
+        }
+        Statement::String { address, text } => {
+          self.compile_string(address.as_deref(), &text)?;
+          self.last_opcode = 0;
+          self.pos += 1;
+        }
+        Statement::Var { name, declaration } => {
+          self.declare_var(name, declaration)?;
+          self.last_opcode = 0;
+          self.pos += 1;
+        }
+        Statement::Directive(Directive::Sempre) => {
+          match self.macro_jump.top() {
+            Ok(top) => self.pos = top.into(),
+            Err(_) => self.pos += 1,
           }
-          inner_spaces.push_str(" +->");
         }
-        if !self.is_preload {
-          self.infra.color_print(inner_spaces, 40);
-          self.infra.color_print(
-            format!(" Compiling {file_path}\n"),
-            match self.inner {
-              0 => 2,
-              _ => 2,
-            },
+        Statement::Directive(Directive::Pare) => {
+          let _ = self.macro_current.pop();
+          self.pos += 1;
+        }
+        Statement::Include(file_path) => {
+          self.include(&file_path)?;
+        }
+        Statement::Macro(macro_path) => {
+          self.expand_macro(&node, &macro_path)?;
+        }
+        Statement::Placeholder => {
+          return diagnostic!(
+            self,
+            MacroExpression,
+            self.pos,
+            &node.text(),
+            "Macro params can only be used inside a macro"
           );
         }
-        let mut compiler = compiler!(file_path, self.infra);
-
-        compiler.set_files(self.files.clone());
-        compiler.set_preload(self.is_preload);
-        compiler.inner_in(self.inner);
-        compiler.set_offset(self.current_command + self.offset);
-
-        let buffer = compiler.compile()?;
+        // Implements the push abreviation
+        Statement::Param if self.last_opcode == 3 => {
+          let param = Param::parse(&node.tokens)
+            .map_err(|err| self.syntax_error(err))?;
+          self
+            .push_command(3, [Some((param, self.pos)), None, None])?;
+          self.map_command(self.pos);
+          self.pos += 1;
+        }
+        Statement::Param => {
+          let token = &node.tokens[0].text;
+          return diagnostic!(
+            self,
+            InvalidToken,
+            self.pos,
+            token,
+            "Invalid token for opcode '{}'",
+            token
+          );
+        }
+        Statement::Opcode(opcode) => {
+          self.compile_opcode(&node, opcode)?;
+        }
+      }
+    }
+    Ok(())
+  }
 
-        self.current_command += buffer.len() / 15;
-        for i in buffer {
-          self.buffer.push(i);
+  fn include(&mut self, file_path: &str) -> Result<(), IError> {
+    let mut inner_spaces = String::from("");
+    if self.inner > 0 {
+      if self.inner > 1 {
+        for _i in 0..self.inner {
+          inner_spaces.push_str("  ");
         }
-        self.last_opcode = 0;
-        self.pos += 1;
-        continue;
-      // MACRO LOGIC
-      } else if string.contains("robsons") {
-        let split: Vec<&str> = string.split('[').collect();
-        let split2: Vec<&str> = string.split(']').collect();
+      }
+      inner_spaces.push_str(" +->");
+    }
+    self.infra.color_print(inner_spaces, 40);
+    self.infra.color_print(
+      format!(" Compiling {file_path}\n"),
+      match self.inner {
+        0 => 2,
+        _ => 2,
+      },
+    );
+    let mut compiler = compiler!(self, file_path, Include, self.pos);
+
+    compiler
+      .compiled_stack(self.compiled_stack.clone(), &self.path)
+      .map_err(|err| {
+        self.locate(
+          err,
+          DiagnosticCode::IncludeFailed,
+          self.pos,
+          file_path,
+        )
+      })?;
+    compiler.inner_in(self.inner);
+    compiler.set_offset(self.current_command + self.offset);
+    compiler.data_start = self.data_start;
+    compiler.next_address = self.next_address;
+
+    let buffer = compiler.compile().map_err(|err| {
+      self.locate(
+        err,
+        DiagnosticCode::IncludeFailed,
+        self.pos,
+        file_path,
+      )
+    })?;
 
-        if split.len() == 2 && split2.len() == 2 {
-          let inside: Vec<&str> =
-            split2[0][1..].split("robsons").collect();
+    self.adopt_patches(&mut compiler, self.pos);
+    self.current_command += compiler.current_command;
+    for i in buffer {
+      self.buffer.push(i);
+    }
+    self.inherit(&mut compiler);
+    self.last_opcode = 0;
+    self.pos += 1;
+    Ok(())
+  }
 
-          if inside.len() != 2 {
-            return ierror!("Malformated robsons macro at {string}");
-          }
-          if inside[1].trim().is_empty() {
-            return ierror!("Malformated robsons macro at {string}");
-          }
+  fn expand_macro(
+    &mut self,
+    node: &Node,
+    macro_path: &str,
+  ) -> Result<(), IError> {
+    let string = node.text();
+    let macro_line = self.pos;
 
-          let mut compiler = compiler!(inside[1].trim(), self.infra);
+    let mut compiler = compiler!(self, macro_path, Macro, macro_line);
 
-          let params_count = compiler.get_file_params()?;
+    let params_count = compiler.get_file_params()?;
 
-          let mut params: HashMap<String, String> = HashMap::new();
-          for i in params_count {
-            self.pos += 1;
-            if self.verify_index_overflow(self.pos) {
-              return ierror!(
-                "Missing params command of line {}",
-                self.pos - i as usize,
-              );
-            }
+    let mut params: HashMap<String, String> = HashMap::new();
+    for (found, i) in params_count.iter().enumerate() {
+      self.pos += 1;
+      if self.verify_index_overflow(self.pos) {
+        return diagnostic!(
+          self,
+          MissingParam,
+          macro_line,
+          &string,
+          "Macro '{}' expects {} params, found {}",
+          macro_path,
+          params_count.len(),
+          found
+        );
+      }
 
-            let mut string = self.lines[self.pos].to_owned();
+      let mut string = self.line_text(self.pos);
 
-            replace_params!(self, string);
+      replace_params!(self, string);
 
-            sanitize_param!(self, string);
+      sanitize_param!(self, string, self.pos);
+
+      if string.trim().is_empty() {
+        return diagnostic!(
+          self,
+          MissingParam,
+          macro_line,
+          macro_path,
+          "Macro '{}' expects {} params, found {}",
+          macro_path,
+          params_count.len(),
+          found
+        );
+      }
+      let update = params.insert(format!("{i}$ROBSON"), string);
+
+      if update.is_some() {
+        return diagnostic!(
+          self,
+          MalformedRequirement,
+          macro_line,
+          macro_path,
+          "Duplicated param {} in '{}'",
+          i,
+          macro_path
+        );
+      }
+    }
 
-            if string.trim().is_empty() {
-              return ierror!(
-                "Missing params command of line {}",
-                self.pos - i as usize,
-              );
-            }
-            let update = params.insert(format!("{i}$ROBSON"), string);
+    compiler.set_macro_params(params);
+    compiler
+      .compiled_stack(self.compiled_stack.clone(), &self.path)
+      .map_err(|err| {
+        self.locate(
+          err,
+          DiagnosticCode::IncludeFailed,
+          macro_line,
+          macro_path,
+        )
+      })?;
+    compiler.inner_in(self.inner);
+    compiler.set_offset(self.current_command + self.offset);
+    compiler.data_start = self.data_start;
+    compiler.next_address = self.next_address;
+
+    let buffer = compiler.compile().map_err(|err| {
+      self.locate(
+        err,
+        DiagnosticCode::IncludeFailed,
+        macro_line,
+        macro_path,
+      )
+    })?;
+
+    self.adopt_patches(&mut compiler, macro_line);
+    self.current_command += compiler.current_command;
+    for i in buffer {
+      self.buffer.push(i);
+    }
+    self.inherit(&mut compiler);
+    self.last_opcode = 0;
+    self.pos += 1;
+    Ok(())
+  }
 
-            if update.is_some() {
-              return ierror!("Duplicated param at in {}", self.path);
-            }
-          }
+  /// Takes the params a compiled file left, they use the labels of
+  /// this file given as macro params or a var address
+  fn adopt_patches(&mut self, compiler: &mut Compiler, line: usize) {
+    for mut patch in compiler.patches.drain(..) {
+      patch.at += self.buffer.len();
+      patch.expr.labels_mut(&mut |name| {
+        if name.starts_with('^') {
+          name.remove(0);
+        }
+      });
+      patch.line = line;
+      self.patches.push(patch);
+    }
+  }
 
-          compiler.set_macro_params(params);
-          compiler.set_files(self.files.clone());
-          compiler.set_preload(self.is_preload);
-          compiler.inner_in(self.inner);
-          compiler.set_offset(self.current_command + self.offset);
+  fn compile_opcode(
+    &mut self,
+    node: &Node,
+    opcode: usize,
+  ) -> Result<(), IError> {
+    let string = node.text();
+    let opcode_line = self.pos;
+    if opcode >= self.opcode_params.len() {
+      return diagnostic!(
+        self,
+        InvalidOpcode,
+        self.pos,
+        &string,
+        "Invalid opcode {}",
+        opcode
+      );
+    }
+    let mut params = [None, None, None];
+    let param_count = self.opcode_params[opcode];
+    for i in 0..param_count {
+      self.pos += 1;
+      if self.verify_index_overflow(self.pos) {
+        return diagnostic!(
+          self,
+          MissingParam,
+          opcode_line,
+          &string,
+          "Opcode {} expects {} params, found {}",
+          opcode,
+          param_count,
+          i
+        );
+      }
+      let mut string = self.line_text(self.pos);
 
-          let buffer = compiler.compile()?;
+      replace_params!(self, string);
 
-          self.current_command += buffer.len() / 15;
-          for i in buffer {
-            self.buffer.push(i);
-          }
-          self.last_opcode = 0;
-          self.pos += 1;
-          continue;
-        }
+      let tokens = tokenize(self.pos, &string);
+      if tokens.is_empty() {
+        return diagnostic!(
+          self,
+          MissingParam,
+          opcode_line,
+          "",
+          "Opcode {} expects {} params, found {}",
+          opcode,
+          param_count,
+          i
+        );
       }
+      let param = Param::parse(&tokens)
+        .map_err(|err| self.syntax_error(err))?;
+      params[i as usize] = Some((param, self.pos));
+    }
 
-      // Implements the push abreviation
-      if self.last_opcode == 3 && !string.contains("robson") {
-        self.push_command(
-          3,
-          [string.to_owned(), "".to_owned(), "".to_owned()],
-        )?;
-        self.pos += 1;
-        continue;
-      }
+    //compile and update command
+    self.push_command(opcode as u8, params)?;
+    self.map_command(opcode_line);
 
-      //get params and opcodes
-      let mut opcode: u8 = 0;
-      let mut params: [String; 3] =
-        ["".to_owned(), "".to_owned(), "".to_owned()];
+    self.pos += 1;
 
-      let spaces: Vec<&str> = string.split(' ').collect();
+    self.last_opcode = opcode as u8;
+    Ok(())
+  }
 
-      for i in spaces {
-        if i != "robson" {
-          return ierror!(
-            "Invalid token for opcode in line {}, '{}'",
-            self.pos + 1,
-            i
-          );
+  /// Pushes `text` with its first char on top, as `load_string`
+  /// leaves it, or stores it zero terminated from `address`, as the
+  /// string `input` does. A char is a push and a set when stored
+  fn compile_string(
+    &mut self,
+    address: Option<&str>,
+    text: &str,
+  ) -> Result<(), IError> {
+    let line = self.pos;
+    match address {
+      None => {
+        for char in text.chars().rev() {
+          let char = value_param(char_to_u32(char).to_string(), line);
+          self.push_command(3, [char, None, None])?;
+          self.map_command(line);
         }
-        opcode += 1;
-      }
-      if opcode as usize >= self.opcode_params.len() {
-        return ierror!("Invalid opcode of line {}", self.pos + 1);
       }
-      let param_count = self.opcode_params[opcode as usize];
-      for i in 0..param_count {
-        self.pos += 1;
-        if self.verify_index_overflow(self.pos) {
-          return ierror!(
-            "Missing params command of line {}",
-            self.pos - i as usize,
-          );
-        }
-        let mut string = self.lines[self.pos].to_owned();
-
-        replace_params!(self, string);
-
-        if string.trim().is_empty() {
-          return ierror!(
-            "Missing params command of line {}",
-            self.pos - i as usize,
-          );
+      Some(address) => {
+        for (i, char) in text.chars().chain(['\0']).enumerate() {
+          let address = match i {
+            0 => address.to_owned(),
+            _ => format!("({address}) + {i}"),
+          };
+          self.store(char_to_u32(char).to_string(), address)?;
         }
-        params[i as usize] = string;
       }
-
-      //update and compile command
-      self.pos += 1;
-
-      self.push_command(opcode, params)?;
-
-      self.last_opcode = opcode;
     }
-    Ok(self.buffer.clone())
+    Ok(())
   }
 
-  pub fn get_cached_macro_size(
+  /// A push of `value` and a set to `address`, both parsed as `comeu`
+  fn store(
     &mut self,
-    path: &str,
-    command_number: usize,
-    mut pos: usize,
-  ) -> Result<(usize, usize), IError> {
-    match self.files.get(path) {
-      Some(a) => Ok(*a),
-      None => {
-        let mut compiler = compiler!(path, self.infra);
+    value: String,
+    address: String,
+  ) -> Result<(), IError> {
+    let line = self.pos;
+    self.push_command(3, [value_param(value, line), None, None])?;
+    self.map_command(line);
+    self
+      .push_command(10, [value_param(address, line), None, None])?;
+    self.map_command(line);
+    Ok(())
+  }
 
-        let params_count = compiler.get_file_params()?;
-        let mut params = HashMap::new();
+  fn init(&mut self, offset: usize, value: Expr, r#type: Type) {
+    self.inits.push(Init {
+      offset,
+      value,
+      r#type,
+      path: self.path.clone(),
+      line: self.pos + 1,
+      source: self.lines[self.pos].clone(),
+      origins: self.origins.clone(),
+    });
+  }
 
-        pos += 1;
+  /// The value of a var, left as an expression while it uses the
+  /// address of a var
+  fn var_value(&self, text: &str) -> Result<Expr, IError> {
+    let mut expr = self.expression(text, self.pos)?;
+    expr.resolve(&|name| self.label(name));
+    let mut undefined = None;
+    expr.labels_mut(&mut |name| {
+      if name != DATA_LABEL {
+        undefined.get_or_insert(name.clone());
+      }
+    });
+    if let Some(name) = undefined {
+      let err = ExprError::UndefinedLabel(name);
+      return Err(self.expression_error(err, self.pos, text));
+    }
+    if expr.has_label(&|a| a == DATA_LABEL) {
+      return Ok(expr);
+    }
+    expr
+      .eval(&|name| self.label(name))
+      .map(Expr::Number)
+      .map_err(|err| self.expression_error(err, self.pos, text))
+  }
 
-        for i in &params_count {
-          if self.verify_index_overflow(pos) {
-            return ierror!("Missing param in line {}", pos);
+  /// Gives `name` the next free cells of the whole compilation, its
+  /// value is set once when the program starts. Scalars are always
+  /// set, so the cell has the declared type
+  fn declare_var(
+    &mut self,
+    name: String,
+    declaration: Declaration,
+  ) -> Result<(), IError> {
+    let key = format!("&{name}");
+    if self.constants.contains_key(&key) {
+      return diagnostic!(
+        self,
+        DuplicatedVariable,
+        self.pos,
+        &name,
+        "Duplicated var '{}'",
+        name
+      );
+    }
+    let offset = self.next_address;
+    let (size, r#type) = match &declaration {
+      Declaration::Scalar { r#type, value } => {
+        let initial = match value {
+          Some(value) => self.var_value(value)?,
+          None => Expr::Number(TypedByte::default()),
+        };
+        self.init(offset, initial, *r#type);
+        (1, Some(*r#type))
+      }
+      Declaration::Array { size, text } => {
+        let length = self
+          .expression(size, self.pos)?
+          .eval(&|name| self.label(name))
+          .map_err(|err| {
+            self.expression_error(err, self.pos, size)
+          })?;
+        let length = match length.r#type {
+          Type::Usigned if length.force_u32() > 0 => {
+            length.force_u32() as usize
           }
-          let tmp = self.is_preload;
-          self.is_preload = true;
-          let mut line = self.lines[pos].clone();
-
-          sanitize_param!(self, line);
-
-          self.is_preload = tmp;
-
-          if line.is_empty() {
-            return ierror!("Missing param in line {}", pos);
+          _ => {
+            return diagnostic!(
+              self,
+              MalformedVariable,
+              self.pos,
+              size,
+              "Invalid size '{}' for '{}', expected a positive u32",
+              length,
+              name
+            );
           }
-
-          params.insert(format!("{i}$ROBSON"), line);
-          pos += 1;
-        }
-
-        compiler.set_offset(command_number);
-        compiler.set_preload(true);
-        compiler.inner_in(self.inner);
-        compiler.set_files(self.files.clone());
-        compiler.set_macro_params(params);
-
-        compiler
-          .compiled_stack(self.compiled_stack.clone(), &self.path)?;
-
-        match compiler.compile() {
-          Ok(_) => {
-            // inherit the compiled files
-            self.files = compiler.files.clone();
-
-            if compiler.is_static {
-              self.files.insert(
-                path.to_owned(),
-                (compiler.current_command, params_count.len()),
-              );
-            }
-            Ok((compiler.current_command, params_count.len()))
+        };
+        if let Some(text) = text {
+          if text.chars().count() >= length {
+            return diagnostic!(
+              self,
+              MalformedVariable,
+              self.pos,
+              &name,
+              "'{}' has {} cells, the text and its zero need {}",
+              name,
+              length,
+              text.chars().count() + 1
+            );
+          }
+          for (i, char) in text.chars().chain(['\0']).enumerate() {
+            let char = Expr::Number(char_to_u32(char).into());
+            self.init(offset + i, char, Type::Usigned);
           }
-          Err(err) => Err(err),
         }
+        (length, None)
       }
-    }
+    };
+    self.next_address += size;
+    let address = Expr::Binary(
+      Operator::Add,
+      Box::new(Expr::Label(DATA_LABEL.to_owned())),
+      Box::new(Expr::Number((offset as u32).into())),
+    );
+    self.constants.insert(key, address);
+    // moved after the cells written by hand once they are known
+    self.memory_map.variables.push(Variable {
+      name,
+      address: offset,
+      size,
+      r#type,
+      path: self.path.clone(),
+      line: self.pos + 1,
+    });
+    Ok(())
   }
 
-  pub fn get_cached_robsons_size(
-    &mut self,
-    path: &str,
-    command_number: usize,
-  ) -> Result<(usize, usize), IError> {
-    match self.files.get(path) {
-      Some(a) => Ok(*a),
-      None => {
-        // compile file and cache it
-        let mut compiler = compiler!(path, self.infra);
-
-        self.infra.color_print(format!("Preloading {path}\n"), 14);
-
-        compiler.set_offset(command_number);
-        compiler.set_preload(true);
-        compiler.inner_in(self.inner);
-        compiler.set_files(self.files.clone());
-
-        compiler
-          .compiled_stack(self.compiled_stack.clone(), &self.path)?;
-
-        match compiler.compile() {
-          Ok(_) => {
-            // inherit the compiled files
-            self.files = compiler.files.clone();
-            if compiler.is_static {
-              self.files.insert(
-                path.to_owned(),
-                (compiler.current_command, 0),
-              );
-            }
+  fn define_label(&mut self, value: String) -> Result<(), IError> {
+    if self.names.contains_key(&value) {
+      return diagnostic!(
+        self,
+        DuplicatedLabel,
+        self.pos,
+        &value,
+        "Duplicated alias '{}'",
+        value
+      );
+    }
+    if self.debug {
+      self.infra.println(format!("{}: {}", value, self.pos + 1));
+    }
+    self.names.insert(value, self.current_command + self.offset);
+    Ok(())
+  }
 
-            Ok((compiler.current_command, 0))
-          }
-          Err(err) => Err(err),
+  /// Fills the params that used a label before it was defined. A
+  /// label starting with `^` is a label of the file that expanded the
+  /// macro, those params are left to it, as the var addresses until
+  /// the vars are placed
+  fn backpatch(&mut self) -> Result<(), IError> {
+    for mut patch in std::mem::take(&mut self.patches) {
+      if patch.expr.has_label(&|a| {
+        (a.starts_with('^') && self.macro_params.is_some())
+          || (a == DATA_LABEL && self.data_start.is_none())
+      }) {
+        patch.expr.resolve(&|name| self.label(name));
+        self.patches.push(patch);
+        continue;
+      }
+      match patch.expr.eval(&|name| self.label(name)) {
+        Ok(value) => {
+          let width = param_width(value.r#type as usize);
+          self.buffer[patch.at..patch.at + width]
+            .copy_from_slice(&value.value[..width]);
+        }
+        Err(err) => {
+          let err = self.expression_error(err, patch.line, "");
+          self.record(err)?;
         }
       }
     }
+    Ok(())
   }
 
-  pub fn start_command_alias(&mut self) -> Result<(), IError> {
-    struct LastCommand {
-      value: u8,
-      pos: usize,
-    }
-    let mut command_number = 0;
-    let mut last_command = LastCommand { pos: 0, value: 0 };
-    if self.macro_params.is_some() {
-      self.pos += 1;
+  /// Constants are folded when defined, unless they use a label
+  /// defined later
+  fn define_const(
+    &mut self,
+    name: String,
+    value: &str,
+  ) -> Result<(), IError> {
+    if self.constants.contains_key(&name) {
+      return diagnostic!(
+        self,
+        DuplicatedConstant,
+        self.pos,
+        &name,
+        "Duplicated const '{}'",
+        name
+      );
     }
-
-    loop {
-      if self.verify_index_overflow(self.pos) {
-        break;
+    let mut expr = self.expression(value, self.pos)?;
+    match expr.eval(&|name| self.label(name)) {
+      Ok(value) => expr = Expr::Number(value),
+      Err(ExprError::UndefinedLabel(_)) => {}
+      Err(err) => {
+        return Err(self.expression_error(err, self.pos, value))
       }
+    }
+    self.constants.insert(name, expr);
+    Ok(())
+  }
 
-      let i = &self.lines[self.pos];
+  fn label(&self, name: &str) -> Option<u32> {
+    if name == DATA_LABEL {
+      return self.data_start.map(|a| a as u32);
+    }
+    self.names.get(name).map(|a| *a as u32)
+  }
 
-      let mut string = Self::remove_comments(i).trim().to_owned();
+  fn expression(
+    &self,
+    text: &str,
+    line: usize,
+  ) -> Result<Expr, IError> {
+    Expr::parse(text, &self.constants)
+      .map_err(|err| self.expression_error(err, line, text))
+  }
 
-      if string.is_empty() {
-        self.pos += 1;
-        continue;
+  fn expression_error(
+    &self,
+    err: ExprError,
+    line: usize,
+    text: &str,
+  ) -> IError {
+    let (code, token, message) = match err {
+      ExprError::UndefinedLabel(name) if name == DATA_LABEL => (
+        DiagnosticCode::InvalidExpression,
+        text.to_owned(),
+        "Var addresses are only known once every file is compiled"
+          .to_owned(),
+      ),
+      ExprError::UndefinedLabel(name) => (
+        DiagnosticCode::UndefinedLabel,
+        format!(":{name}"),
+        format!("Cant find '{}' in {}", name, self.path),
+      ),
+      ExprError::UndefinedConstant(ref name) => (
+        DiagnosticCode::UndefinedConstant,
+        name.clone(),
+        err.to_string(),
+      ),
+      ExprError::UndefinedVariable(ref name) => (
+        DiagnosticCode::UndefinedVariable,
+        format!("&{name}"),
+        err.to_string(),
+      ),
+      ExprError::InvalidNumber(ref word, _) => {
+        (DiagnosticCode::InvalidNumber, word.clone(), err.to_string())
       }
+      ExprError::Invalid(_) => (
+        DiagnosticCode::InvalidExpression,
+        text.to_owned(),
+        err.to_string(),
+      ),
+    };
+    self.diagnostic(code, line, &token, message).into()
+  }
 
-      replace_params!(self, string);
-
-      if string == "SEMPRE#ROBSON" {
-        if self.macro_jump.sx != 0 {
-          self.pos = self.macro_jump.top().into();
-          continue;
-        } else {
-          self.pos += 1;
-          continue;
-        }
-      }
-      if string == "PARE#ROBSON" {
-        self.macro_current.pop();
-        self.pos += 1;
-        continue;
-      }
+  /// The parsed line at `pos`
+  fn node(&self, pos: usize) -> Result<Node, IError> {
+    self.nodes[pos]
+      .clone()
+      .map_err(|err| self.syntax_error(err))
+  }
 
-      //add alias if it is an alias
-      if string.ends_with(':') {
-        let value = string.trim().replace(':', "");
-        if self.names.get(&value).is_some() {
-          return ierror!("Duplicated alias: {}", value);
-        }
-        if self.debug {
-          self.infra.println(format!("{}: {}", value, self.pos + 1));
-        }
-        self.names.insert(value, command_number + self.offset);
-      } else {
-        //if is not an check what it is
-        if string.starts_with("robsons") {
-          //if is an include compile the include to get the correct value of the aliases
-          let splited: Vec<&str> = string.split(' ').collect();
-          if splited.len() != 2 {
-            return ierror!("Malformated robsons");
-          }
-          let path = splited[1];
-
-          // get offset from cache if possible
-          let (new_offset, _) = match self
-            .get_cached_robsons_size(path, command_number)
-          {
-            Ok(a) => a,
-            Err(err) => return Err(err),
-          };
+  /// Parses a line at `pos` after its macro params were replaced
+  fn reparse(&self, string: &str) -> Result<Node, IError> {
+    parse_line(self.pos, string).map_err(|err| self.syntax_error(err))
+  }
 
-          command_number += new_offset;
-        } else if string.contains("robsons") {
-          let split: Vec<&str> = string.split('[').collect();
-          let split2: Vec<&str> = string.split(']').collect();
-          if split2.len() == 2 && split.len() == 2 {
-            let inside: Vec<&str> =
-              split2[0][1..].split("robsons").collect();
-
-            if inside.len() != 2 {
-              return ierror!(
-                "Malformated robsons macro at {}",
-                string
-              );
-            }
-            if inside[1].trim().is_empty() {
-              return ierror!(
-                "Malformated robsons macro at {}",
-                string
-              );
-            }
+  /// The tokens of the line at `pos`, without the comment
+  fn line_text(&self, pos: usize) -> String {
+    Node {
+      statement: Statement::Param,
+      tokens: tokenize(pos, &self.lines[pos]),
+    }
+    .text()
+  }
 
-            let (new_offset, params_count) = match self
-              .get_cached_macro_size(
-                inside[1].trim(),
-                command_number,
-                self.pos,
-              ) {
-              Ok(a) => a,
-              Err(err) => return Err(err),
-            };
-            last_command.value = 0;
-            last_command.pos = self.pos;
-            self.pos += params_count;
-            command_number += new_offset
-          }
-        } else if string.starts_with("robson") {
-          // if is a command just add it
-          command_number += 1;
-          let mut opcode: u8 = 0;
-          let spaces: Vec<&str> = string.split(' ').collect();
-
-          for i in spaces {
-            if i != "robson" {
-              return ierror!(
-                "invalid token for opcode in line {}, '{}'",
-                self.pos + 1,
-                i
-              );
-            }
-            opcode += 1;
-          }
-          last_command.value = opcode;
-          last_command.pos = self.pos;
-        } else if last_command.value == 3
-          && last_command.pos + 1 != self.pos
-        {
-          command_number += 1;
-        }
-      }
-      self.pos += 1;
+  /// Points at the span when it still matches the source, replaced
+  /// macro params don't
+  fn syntax_error(&self, err: SyntaxError) -> IError {
+    let mut diagnostic = self.diagnostic(
+      err.code,
+      err.span.line,
+      &err.token,
+      err.message,
+    );
+    let source =
+      self.lines.get(err.span.line).map_or("", |a| a.as_str());
+    if source.get(err.span.start..err.span.end) == Some(&err.token) {
+      diagnostic.columns = err.span.columns(source);
     }
-    self.macro_current = Stack::new();
-    self.macro_jump = Stack::new();
-    self.pos = 0;
-    Ok(())
+    diagnostic.into()
   }
-  pub fn remove_comments(string: &str) -> &str {
-    let mut res = string;
 
-    let comments = string.split(';').collect::<Vec<&str>>();
-    if !comments.is_empty() {
-      res = comments[0].trim();
+  /// Whether recovery can resume at the line `pos`
+  fn starts_statement(&self, pos: usize) -> bool {
+    match &self.nodes[pos] {
+      Ok(node) => matches!(
+        node.statement,
+        Statement::Label(_)
+          | Statement::Opcode(_)
+          | Statement::Include(_)
+          | Statement::Macro(_)
+          | Statement::Const { .. }
+          | Statement::String { .. }
+          | Statement::Var { .. }
+      ),
+      Err(_) => true,
     }
-    res
   }
 
   fn verify_index_overflow(&self, pos: usize) -> bool {
     self.lines.len() <= pos
   }
 
+  /// Each param comes with the 0-based line it was read from, the
+  /// missing ones are zeroed
   pub fn push_command(
     &mut self,
     opcode: u8,
-    params: [String; 3],
+    params: [Option<(Param, usize)>; 3],
   ) -> Result<(), IError> {
-    self.buffer.push(opcode);
-
-    let (param1, param1_kind, param1_types, param1_convert) =
-      self.get_kind_value(params[0].trim())?;
-    let param1 = param1.value;
-
-    let (param2, param2_kind, param2_types, param2_convert) =
-      self.get_kind_value(params[1].trim())?;
-    let param2 = param2.value;
-
-    let (param3, param3_kind, param3_types, param3_convert) =
-      self.get_kind_value(params[2].trim())?;
-    let param3 = param3.value;
-
-    self.buffer.push(utils::create_kind_byte(
-      param1_kind,
-      param2_kind,
-      param3_kind,
-      create_two_bits([param1_convert, param2_convert]),
-    ));
-
-    for i in param1 {
-      self.buffer.push(i);
-    }
-    for i in param2 {
-      self.buffer.push(i);
+    let mut values = [(TypedByte::default(), 0, 0, false); 3];
+    let mut unresolved = Vec::new();
+    for (i, (value, param)) in
+      values.iter_mut().zip(&params).enumerate()
+    {
+      let Some((param, line)) = param else {
+        continue;
+      };
+      let expr = self.param_expr(param, *line)?;
+      let r#type = expr.r#type() as u8;
+      let known = match expr.eval(&|name| self.label(name)) {
+        Ok(known) => known,
+        Err(ExprError::UndefinedLabel(_)) => {
+          unresolved.push((i, expr, *line));
+          TypedByte::default()
+        }
+        Err(err) => {
+          return Err(self.expression_error(
+            err,
+            *line,
+            &param.value.text,
+          ))
+        }
+      };
+      *value = (known, param.kind.bits(), r#type, param.convert);
     }
-    for i in param3 {
-      self.buffer.push(i);
+    let [(param1, param1_kind, param1_types, param1_convert), (param2, param2_kind, param2_types, param2_convert), (param3, param3_kind, param3_types, param3_convert)] =
+      values.map(|(value, kind, r#type, convert)| {
+        (value.value, kind, r#type, convert)
+      });
+
+    let record = Record {
+      opcode,
+      kind_byte: utils::create_kind_byte(
+        param1_kind,
+        param2_kind,
+        param3_kind,
+        create_two_bits([param1_convert, param2_convert]),
+      ),
+      types_bytes: create_types_bytes(
+        [param1_types, param2_types, param3_types],
+        param3_convert,
+      ),
+      params: [param1, param2, param3],
+    };
+    for (i, expr, line) in unresolved {
+      self.patches.push(Patch {
+        at: self.buffer.len() + record.param_offset(i),
+        expr,
+        line,
+      });
     }
-    self.buffer.push(create_kind_byte(
-      param1_types,
-      param2_types,
-      param3_types,
-      create_two_bits([param3_convert, false]),
-    ));
+    record.encode(&mut self.buffer);
     self.current_command += 1;
     Ok(())
   }
+  /// Parses a param read from the 0-based `line`
   pub fn get_kind_value(
     &self,
     parameter: &str,
+    line: usize,
   ) -> Result<(TypedByte, u8, u8, bool), IError> {
-    if parameter.is_empty() {
+    let tokens = tokenize(line, parameter);
+    if tokens.is_empty() {
       return Ok((0u32.into(), 0, 0, false));
     }
+    let param =
+      Param::parse(&tokens).map_err(|err| self.syntax_error(err))?;
+    self.param_value(&param, line)
+  }
 
-    let splited: Vec<&str> = parameter.split(' ').collect();
-
-    if splited.len() < 2 {
-      return ierror!(
-        "Malformated param at line {}\n'{}' - {}",
-        self.pos,
-        self.lines[self.pos],
-        self.path
-      );
-    }
-
-    if splited.len() > 3 {
-      return ierror!(
-        "Malformated comeu in line {}\n'{}' - {}",
-        self.pos,
-        parameter,
-        self.path
-      );
-    }
+  fn param_value(
+    &self,
+    param: &Param,
+    line: usize,
+  ) -> Result<(TypedByte, u8, u8, bool), IError> {
+    let value = self
+      .param_expr(param, line)?
+      .eval(&|name| self.label(name))
+      .map_err(|err| {
+        self.expression_error(err, line, &param.value.text)
+      })?;
+    Ok((value, param.kind.bits(), value.r#type as u8, param.convert))
+  }
 
-    let mut convert = false;
-    if splited.len() == 3 {
-      if splited[2] == "robson" {
-        convert = true;
-      } else {
-        return ierror!(
-          "Malformated param at line {}, expected 'robson'\n{}",
-          self.pos,
-          self.lines[self.pos]
+  /// Addresses must be unsigned and `lambeu` starts with a label
+  pub fn param_expr(
+    &self,
+    param: &Param,
+    line: usize,
+  ) -> Result<Expr, IError> {
+    let text = param.value.text.as_str();
+    if param.kind == ParamKind::Lambeu {
+      let Some(name) = text.strip_prefix(':') else {
+        return diagnostic!(
+          self,
+          MalformedParam,
+          line,
+          text,
+          "Malformated name '{}', expected ':name'",
+          text
         );
+      };
+      // a lone label keeps any char its definition allows
+      if self.names.contains_key(name)
+        || !name.contains(|a: char| {
+          a.is_whitespace() || OPERATORS.contains(&a)
+        })
+      {
+        return Ok(Expr::Label(name.to_owned()));
       }
     }
-
-    match splited[0] {
-      "comeu" => {
-        let mut value = splited[1].trim().to_owned();
-        let first = value.chars().collect::<Vec<char>>()[0];
-        match first {
-          'f' => {
-            value = value.replace('f', "");
-            Ok((value.parse::<f32>()?.into(), 0, 2, convert))
-          }
-          'i' => {
-            value = value.replace('i', "");
-            Ok((value.parse::<i32>()?.into(), 0, 1, convert))
-          }
-          _ => Ok((
-            splited[1].trim().parse::<u32>()?.into(),
-            0,
-            0,
-            convert,
-          )),
-        }
-      }
-      "chupou" => {
-        let value = splited[1].parse::<u32>()?;
-        Ok((value.into(), 1, 0, convert))
-      }
-      "fudeu" => {
-        let value = splited[1].trim().parse::<u32>()?;
-        Ok((value.into(), 2, 0, convert))
-      }
-      "lambeu" => {
-        let value = splited[1].trim();
-        if value.chars().collect::<Vec<char>>()[0] != ':' {
-          return ierror!(
-            "Malformated name in command at {}, '{}'",
-            self.pos,
-            value
-          );
-        }
-        let value = value.replace(':', "");
-
-        let a = self.names.get(&value).ok_or_else(|| {
-          IError::message(&format!(
-            "Cant find '{}' in {}",
-            value, self.path
-          ))
-        })?;
-        Ok(((*a as u32).into(), 0, 0, convert))
-      }
-      "penetrou" => {
-        let value = splited[1].trim().parse::<u32>()?;
-        Ok((value.into(), 3, 0, convert))
-      }
-      token => ierror!(
-        "Unexpect token for param at line {}, '{}'",
-        self.pos,
-        token
-      ),
+    let expr = self.expression(text, line)?;
+    if param.kind != ParamKind::Comeu
+      && expr.r#type() != Type::Usigned
+    {
+      return diagnostic!(
+        self,
+        InvalidNumber,
+        line,
+        text,
+        "Invalid address '{}', expected an unsigned value, found {:?}",
+        text,
+        expr.r#type()
+      );
     }
+    Ok(expr)
   }
 }
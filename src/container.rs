@@ -0,0 +1,274 @@
+use crate::{
+  data_struct::{IError, Records},
+  macros::ierror,
+  utils::{convert_kind_byte, crc32, u32_from_bytes},
+};
+
+pub const MAGIC: [u8; 4] = *b"RBSN";
+pub const FORMAT_VERSION: u16 = 3;
+
+const HEADER_SIZE: usize = 12;
+const ENTRY_SIZE: usize = 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionKind {
+  Code = 1,
+  Symbols = 2,
+  Debug = 3,
+  Sources = 4,
+  Metadata = 5,
+}
+
+/// A compiled program with everything needed to validate it before
+/// running
+///
+/// Layout, all integers little endian:
+/// magic, version u16, section count u16, memory cells u32, then a
+/// table of (kind u32, offset u32, length u32) entries, the section
+/// payloads and a trailing crc32 of every byte before it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Container<'a> {
+  pub version: u16,
+  pub memory: u32,
+  pub source: String,
+  pub code: &'a [u8],
+  pub symbols: Vec<(String, u32)>,
+  pub debug: Option<&'a [u8]>,
+  pub sources: Vec<(String, u32)>,
+}
+
+pub(crate) struct Reader<'a> {
+  bytes: &'a [u8],
+  pos: usize,
+}
+impl<'a> Reader<'a> {
+  pub(crate) fn new(bytes: &'a [u8]) -> Self {
+    Self { bytes, pos: 0 }
+  }
+  pub(crate) fn take(
+    &mut self,
+    len: usize,
+  ) -> Result<&'a [u8], IError> {
+    if self.pos + len > self.bytes.len() {
+      return ierror!("Corrupted bytecode, truncated section");
+    }
+    let slice = &self.bytes[self.pos..self.pos + len];
+    self.pos += len;
+    Ok(slice)
+  }
+  pub(crate) fn u8(&mut self) -> Result<u8, IError> {
+    Ok(self.take(1)?[0])
+  }
+  pub(crate) fn u64(&mut self) -> Result<u64, IError> {
+    let a = self.take(8)?;
+    Ok(u64::from_le_bytes([
+      a[0], a[1], a[2], a[3], a[4], a[5], a[6], a[7],
+    ]))
+  }
+  pub(crate) fn u32(&mut self) -> Result<u32, IError> {
+    let a = self.take(4)?;
+    Ok(u32::from_le_bytes([a[0], a[1], a[2], a[3]]))
+  }
+  pub(crate) fn string(&mut self) -> Result<String, IError> {
+    let len = self.u32()? as usize;
+    String::from_utf8(self.take(len)?.to_vec()).map_err(|_| {
+      IError::message("Corrupted bytecode, invalid utf-8 string")
+    })
+  }
+  fn pairs(&mut self) -> Result<Vec<(String, u32)>, IError> {
+    let count = self.u32()?;
+    let mut pairs = Vec::new();
+    for _ in 0..count {
+      pairs.push((self.string()?, self.u32()?));
+    }
+    Ok(pairs)
+  }
+}
+
+pub(crate) fn push_string(out: &mut Vec<u8>, string: &str) {
+  out.extend((string.len() as u32).to_le_bytes());
+  out.extend(string.as_bytes());
+}
+fn push_pairs(out: &mut Vec<u8>, pairs: &[(String, u32)]) {
+  out.extend((pairs.len() as u32).to_le_bytes());
+  for (name, value) in pairs {
+    push_string(out, name);
+    out.extend(value.to_le_bytes());
+  }
+}
+
+impl<'a> Container<'a> {
+  pub fn new(source: String, code: &'a [u8]) -> Self {
+    Self {
+      version: FORMAT_VERSION,
+      memory: required_memory(code),
+      source,
+      code,
+      symbols: Vec::new(),
+      debug: None,
+      sources: Vec::new(),
+    }
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut metadata = Vec::new();
+    push_string(&mut metadata, &self.source);
+    let mut symbols = Vec::new();
+    push_pairs(&mut symbols, &self.symbols);
+    let mut sources = Vec::new();
+    push_pairs(&mut sources, &self.sources);
+
+    let mut sections: Vec<(SectionKind, &[u8])> = vec![
+      (SectionKind::Metadata, &metadata),
+      (SectionKind::Code, self.code),
+      (SectionKind::Symbols, &symbols),
+      (SectionKind::Sources, &sources),
+    ];
+    if let Some(debug) = self.debug {
+      sections.push((SectionKind::Debug, debug));
+    }
+
+    let mut out = Vec::new();
+    out.extend(MAGIC);
+    out.extend(self.version.to_le_bytes());
+    out.extend((sections.len() as u16).to_le_bytes());
+    out.extend(self.memory.to_le_bytes());
+
+    let mut offset = HEADER_SIZE + sections.len() * ENTRY_SIZE;
+    for (kind, payload) in &sections {
+      out.extend((*kind as u32).to_le_bytes());
+      out.extend((offset as u32).to_le_bytes());
+      out.extend((payload.len() as u32).to_le_bytes());
+      offset += payload.len();
+    }
+    for (_, payload) in &sections {
+      out.extend(*payload);
+    }
+    out.extend(crc32(&out).to_le_bytes());
+    out
+  }
+
+  /// Validates and reads a container, borrowing the code from `bytes`
+  pub fn load(bytes: &'a [u8]) -> Result<Self, IError> {
+    if bytes.len() < HEADER_SIZE + 4 || bytes[0..4] != MAGIC {
+      return ierror!("Not a robson bytecode file, missing magic");
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version != FORMAT_VERSION {
+      return ierror!(
+        "Unsupported bytecode version {}, expected {}",
+        version,
+        FORMAT_VERSION
+      );
+    }
+    let (body, checksum) = bytes.split_at(bytes.len() - 4);
+    let checksum = u32::from_le_bytes([
+      checksum[0],
+      checksum[1],
+      checksum[2],
+      checksum[3],
+    ]);
+    if crc32(body) != checksum {
+      return ierror!("Corrupted bytecode, checksum mismatch");
+    }
+
+    let count = u16::from_le_bytes([bytes[6], bytes[7]]) as usize;
+    let mut reader = Reader {
+      bytes: body,
+      pos: 8,
+    };
+    let memory = reader.u32()?;
+    let mut container = Self {
+      version,
+      memory,
+      source: String::new(),
+      code: &[],
+      symbols: Vec::new(),
+      debug: None,
+      sources: Vec::new(),
+    };
+    let mut has_code = false;
+
+    for _ in 0..count {
+      let kind = reader.u32()?;
+      let offset = reader.u32()? as usize;
+      let len = reader.u32()? as usize;
+      let payload = body
+        .get(offset..offset.saturating_add(len))
+        .ok_or_else(|| {
+          IError::message("Corrupted bytecode, section out of bounds")
+        })?;
+      let mut section = Reader::new(payload);
+      match kind {
+        1 => {
+          has_code = true;
+          container.code = payload;
+        }
+        2 => container.symbols = section.pairs()?,
+        3 => container.debug = Some(payload),
+        4 => container.sources = section.pairs()?,
+        5 => container.source = section.string()?,
+        // sections from newer writers are skipped
+        _ => {}
+      }
+    }
+    if !has_code {
+      return ierror!("Corrupted bytecode, missing code section");
+    }
+    if let Some(Err(offset)) =
+      Records::new(container.code).find(Result::is_err)
+    {
+      return ierror!(
+        "Corrupted bytecode, the code ends inside the record at byte {}",
+        offset
+      );
+    }
+    Ok(container)
+  }
+}
+
+/// The byte offset of every constant address param of the program
+/// and the last cell it can write, in order. A string `input` writes
+/// up to its limit after its address
+pub fn constant_addresses(code: &[u8]) -> Vec<(usize, u32)> {
+  let mut addresses = Vec::new();
+  let mut at = 0;
+  for record in Records::new(code).flatten() {
+    let kinds = convert_kind_byte(record.kind_byte);
+    let value = |i: usize| {
+      let param = record.params[i];
+      u32_from_bytes([param[0], param[1], param[2], param[3]])
+    };
+    for (i, kind) in kinds.iter().take(3).enumerate() {
+      let is_address = *kind == 2
+        || (i == 0
+          && *kind == 0
+          && matches!(record.opcode, 6 | 10 | 12));
+      if !is_address {
+        continue;
+      }
+      let number = kinds[1] == 0 && (1..=3).contains(&value(1));
+      let last = if record.opcode == 6 && i == 0 && !number {
+        match kinds[2] {
+          0 => value(0).saturating_add(value(2)),
+          // a limit read while running isnt known
+          _ => value(0),
+        }
+      } else {
+        value(i)
+      };
+      addresses.push((at + record.param_offset(i), last));
+    }
+    at += record.size();
+  }
+  addresses
+}
+
+/// Smallest memory that holds every constant address of the program
+pub fn required_memory(code: &[u8]) -> u32 {
+  constant_addresses(code)
+    .into_iter()
+    .map(|(_, cell)| cell.saturating_add(1))
+    .max()
+    .unwrap_or(0)
+}
@@ -5,9 +5,14 @@ use std::{
   ops::{Deref, DerefMut},
 };
 
-use crate::utils::{
-  f32_from_bytes, f32_to_bytes, i32_from_bytes, i32_to_bytes,
-  u32_from_bytes, u32_to_bytes,
+use crate::{
+  diagnostic::Diagnostic,
+  utils::{
+    convert_types_bytes, f32_from_bytes, f32_to_bytes,
+    f64_from_bytes, f64_to_bytes, i32_from_bytes, i32_to_bytes,
+    i64_from_bytes, i64_to_bytes, u32_from_bytes, u32_to_bytes,
+    u64_from_bytes, u64_to_bytes, widen,
+  },
 };
 
 // #[derive(Debug, Clone, Copy)]
@@ -39,6 +44,100 @@ use crate::utils::{
 //     )
 //   }
 // }
+/// Bytes of a value, the 32-bit types use the first half
+pub const PARAM_SIZE: usize = 8;
+/// Opcode, kind byte and the two types bytes that start a record
+pub const HEADER_SIZE: usize = 4;
+/// Bytes of a record whose params are all 32-bit, each 64-bit param
+/// takes 4 more
+pub const RECORD_SIZE: usize = HEADER_SIZE + 3 * 4;
+
+/// Bytes a param takes in a record, from its type nibble. Invalid
+/// nibbles take 4 so the verifier can still walk the buffer
+pub const fn param_width(bits: usize) -> usize {
+  if bits >= Type::Usigned64 as usize && bits < TYPES.len() {
+    8
+  } else {
+    4
+  }
+}
+
+/// The fields of a record, params zero extended to `PARAM_SIZE`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record {
+  pub opcode: u8,
+  pub kind_byte: u8,
+  pub types_bytes: [u8; 2],
+  pub params: [[u8; PARAM_SIZE]; 3],
+}
+impl Record {
+  fn widths(&self) -> [usize; 3] {
+    let types = convert_types_bytes(self.types_bytes);
+    [types[0], types[1], types[2]].map(param_width)
+  }
+  /// Where `param` starts from the start of the record
+  pub fn param_offset(&self, param: usize) -> usize {
+    HEADER_SIZE + self.widths()[..param].iter().sum::<usize>()
+  }
+  /// Bytes of the encoded record
+  pub fn size(&self) -> usize {
+    self.param_offset(3)
+  }
+  pub fn encode(&self, out: &mut Vec<u8>) {
+    out.push(self.opcode);
+    out.push(self.kind_byte);
+    out.extend(self.types_bytes);
+    for (param, width) in self.params.iter().zip(self.widths()) {
+      out.extend(&param[..width]);
+    }
+  }
+}
+
+/// Splits a buffer into records, failing with the offset of a record
+/// cut short
+pub struct Records<'a> {
+  buffer: &'a [u8],
+  offset: usize,
+}
+impl<'a> Records<'a> {
+  pub fn new(buffer: &'a [u8]) -> Self {
+    Self { buffer, offset: 0 }
+  }
+}
+impl Iterator for Records<'_> {
+  type Item = Result<Record, usize>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    let start = self.offset;
+    let rest = &self.buffer[start..];
+    if rest.is_empty() {
+      return None;
+    }
+    // nothing follows a record cut short
+    self.offset = self.buffer.len();
+    if rest.len() < HEADER_SIZE {
+      return Some(Err(start));
+    }
+    let mut record = Record {
+      opcode: rest[0],
+      kind_byte: rest[1],
+      types_bytes: [rest[2], rest[3]],
+      params: [[0; PARAM_SIZE]; 3],
+    };
+    let widths = record.widths();
+    let mut at = HEADER_SIZE;
+    for (param, width) in record.params.iter_mut().zip(widths) {
+      let Some(bytes) = rest.get(at..at + width) else {
+        return Some(Err(start));
+      };
+      param[..width].copy_from_slice(bytes);
+      at += width;
+    }
+    self.offset = start + at;
+    Some(Ok(record))
+  }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct Instruction {
   pub opcode: u8,
@@ -73,7 +172,7 @@ impl Instruction {
         (
           TypedByte {
             r#type: Type::Usigned,
-            value: [0; 4],
+            value: [0; 8],
           },
           0,
           false,
@@ -81,7 +180,7 @@ impl Instruction {
         (
           TypedByte {
             r#type: Type::Usigned,
-            value: [0; 4],
+            value: [0; 8],
           },
           0,
           false,
@@ -89,7 +188,7 @@ impl Instruction {
         (
           TypedByte {
             r#type: Type::Usigned,
-            value: [0; 4],
+            value: [0; 8],
           },
           0,
           false,
@@ -101,6 +200,7 @@ impl Instruction {
 #[derive(Debug, Clone)]
 pub struct IError {
   pub error: String,
+  pub diagnostics: Vec<Diagnostic>,
 }
 impl IError {
   pub fn message<T>(error: T) -> Self
@@ -109,28 +209,45 @@ impl IError {
   {
     Self {
       error: error.to_string(),
+      diagnostics: Vec::new(),
+    }
+  }
+  pub fn from_diagnostics(diagnostics: Vec<Diagnostic>) -> Self {
+    let mut error = diagnostics
+      .iter()
+      .map(|a| a.render())
+      .collect::<Vec<String>>()
+      .join("\n\n");
+    if diagnostics.len() > 1 {
+      error.push_str(&format!(
+        "\n\naborting due to {} previous errors",
+        diagnostics.len()
+      ));
     }
+    Self { error, diagnostics }
   }
 }
-impl From<ParseIntError> for IError {
-  fn from(err: ParseIntError) -> Self {
+impl From<Diagnostic> for IError {
+  fn from(diagnostic: Diagnostic) -> Self {
     Self {
-      error: err.to_string(),
+      error: diagnostic.render(),
+      diagnostics: vec![diagnostic],
     }
   }
 }
+impl From<ParseIntError> for IError {
+  fn from(err: ParseIntError) -> Self {
+    Self::message(err)
+  }
+}
 impl From<ParseFloatError> for IError {
   fn from(err: ParseFloatError) -> Self {
-    Self {
-      error: err.to_string(),
-    }
+    Self::message(err)
   }
 }
 impl From<std::io::Error> for IError {
   fn from(err: std::io::Error) -> Self {
-    Self {
-      error: err.to_string(),
-    }
+    Self::message(err)
   }
 }
 impl Display for IError {
@@ -139,15 +256,17 @@ impl Display for IError {
   }
 }
 impl Error for IError {}
+/// A value and its type, 32-bit types only use the first 4 bytes and
+/// keep the rest zeroed
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct TypedByte {
-  pub value: [u8; 4],
+  pub value: [u8; 8],
   pub r#type: Type,
 }
 impl From<u32> for TypedByte {
   fn from(value: u32) -> Self {
     Self {
-      value: u32_to_bytes(value),
+      value: widen(u32_to_bytes(value)),
       r#type: Type::Usigned,
     }
   }
@@ -155,7 +274,7 @@ impl From<u32> for TypedByte {
 impl From<i32> for TypedByte {
   fn from(value: i32) -> Self {
     Self {
-      value: i32_to_bytes(value),
+      value: widen(i32_to_bytes(value)),
       r#type: Type::Signed,
     }
   }
@@ -163,16 +282,40 @@ impl From<i32> for TypedByte {
 impl From<f32> for TypedByte {
   fn from(value: f32) -> Self {
     Self {
-      value: f32_to_bytes(value),
+      value: widen(f32_to_bytes(value)),
       r#type: Type::Floating,
     }
   }
 }
+impl From<u64> for TypedByte {
+  fn from(value: u64) -> Self {
+    Self {
+      value: u64_to_bytes(value),
+      r#type: Type::Usigned64,
+    }
+  }
+}
+impl From<i64> for TypedByte {
+  fn from(value: i64) -> Self {
+    Self {
+      value: i64_to_bytes(value),
+      r#type: Type::Signed64,
+    }
+  }
+}
+impl From<f64> for TypedByte {
+  fn from(value: f64) -> Self {
+    Self {
+      value: f64_to_bytes(value),
+      r#type: Type::Floating64,
+    }
+  }
+}
 
 impl From<[u8; 4]> for TypedByte {
   fn from(value: [u8; 4]) -> Self {
     Self {
-      value,
+      value: widen(value),
       r#type: Type::Usigned,
     }
   }
@@ -180,94 +323,113 @@ impl From<[u8; 4]> for TypedByte {
 impl From<usize> for TypedByte {
   fn from(value: usize) -> Self {
     Self {
-      value: u32_to_bytes(value as u32),
+      value: widen(u32_to_bytes(value as u32)),
       r#type: Type::Usigned,
     }
   }
 }
 impl Into<usize> for TypedByte {
   fn into(self) -> usize {
-    if self.r#type == Type::Usigned {
-      u32_from_bytes(self.value) as usize
-    } else {
-      0
+    match self.r#type {
+      Type::Usigned => u32_from_bytes(*self) as usize,
+      Type::Usigned64 => u64_from_bytes(self.value) as usize,
+      _ => 0,
     }
   }
 }
 impl From<bool> for TypedByte {
   fn from(value: bool) -> Self {
     TypedByte {
-      value: u32_to_bytes(value as u32),
+      value: widen(u32_to_bytes(value as u32)),
       r#type: Type::Usigned,
     }
   }
 }
 
+/// The first 4 bytes, all a 32-bit type uses
 impl Deref for TypedByte {
   type Target = [u8; 4];
   fn deref(&self) -> &Self::Target {
-    &self.value
+    &self.value.as_chunks::<4>().0[0]
   }
 }
 impl Display for TypedByte {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-    write!(
-      f,
-      "{}",
-      match self.r#type {
-        Type::Usigned => format!("{}", u32_from_bytes(self.value)),
-        Type::Signed => format!("i{}", i32_from_bytes(self.value)),
-        Type::Floating => format!("f{}", f32_from_bytes(self.value)),
+    match self.r#type {
+      Type::Usigned => write!(f, "{}", u32_from_bytes(**self)),
+      Type::Signed => write!(f, "i{}", i32_from_bytes(**self)),
+      Type::Floating => write!(f, "f{}", f32_from_bytes(**self)),
+      Type::Usigned64 => {
+        write!(f, "u64:{}", u64_from_bytes(self.value))
+      }
+      Type::Signed64 => {
+        write!(f, "i64:{}", i64_from_bytes(self.value))
       }
-    )
+      Type::Floating64 => {
+        write!(f, "f64:{}", f64_from_bytes(self.value))
+      }
+    }
   }
 }
 
+/// Converts like an `as` cast from the current type to `$type`
+macro_rules! cast {
+  ($self: expr, $type: ty) => {
+    match $self.integer() {
+      Some(a) => TypedByte::from(a as $type),
+      None => TypedByte::from($self.float() as $type),
+    }
+  };
+}
+
 impl TypedByte {
   pub fn force_u32(&self) -> u32 {
-    u32_from_bytes(self.value)
+    u32_from_bytes(**self)
   }
+  /// Integers widened without losing their sign, `None` for floats
+  pub(crate) fn integer(&self) -> Option<i128> {
+    match self.r#type {
+      Type::Usigned => Some(u32_from_bytes(**self) as i128),
+      Type::Signed => Some(i32_from_bytes(**self) as i128),
+      Type::Usigned64 => Some(u64_from_bytes(self.value) as i128),
+      Type::Signed64 => Some(i64_from_bytes(self.value) as i128),
+      Type::Floating | Type::Floating64 => None,
+    }
+  }
+  pub(crate) fn float(&self) -> f64 {
+    match self.r#type {
+      Type::Floating => f32_from_bytes(**self) as f64,
+      Type::Floating64 => f64_from_bytes(self.value),
+      _ => self.integer().unwrap_or_default() as f64,
+    }
+  }
+  /// Integers are truncated or extended and floats saturate at the
+  /// bounds of integer types, the same as `as` in rust
   pub fn convert(&mut self, to_convert: Type) {
-    self.r#type = to_convert;
-
-    match to_convert {
-      Type::Usigned => match self.r#type {
-        Type::Usigned => {}
-        Type::Signed => {
-          self.value = u32_to_bytes(i32_from_bytes(self.value) as u32)
-        }
-        Type::Floating => {
-          self.value = u32_to_bytes(f32_from_bytes(self.value) as u32)
-        }
-      },
-      Type::Signed => match self.r#type {
-        Type::Usigned => {
-          self.value = i32_to_bytes(u32_from_bytes(self.value) as i32)
-        }
-        Type::Signed => {}
-        Type::Floating => {
-          self.value = i32_to_bytes(f32_from_bytes(self.value) as i32)
-        }
-      },
-      Type::Floating => match self.r#type {
-        Type::Usigned => {
-          self.value = f32_to_bytes(u32_from_bytes(self.value) as f32)
-        }
-        Type::Signed => {
-          self.value = f32_to_bytes(i32_from_bytes(self.value) as f32)
-        }
-        Type::Floating => {}
-      },
+    if self.r#type == to_convert {
+      return;
     }
+    *self = match to_convert {
+      Type::Usigned => cast!(self, u32),
+      Type::Signed => cast!(self, i32),
+      Type::Floating => cast!(self, f32),
+      Type::Usigned64 => cast!(self, u64),
+      Type::Signed64 => cast!(self, i64),
+      Type::Floating64 => cast!(self, f64),
+    };
+  }
+  /// Whether the type takes all 8 bytes
+  pub fn is_wide(&self) -> bool {
+    self.r#type >= Type::Usigned64
   }
 }
 
 #[derive(Debug)]
-pub struct Stack<const A: usize> {
-  pub vec: [TypedByte; A],
+pub struct Stack {
+  pub vec: Vec<TypedByte>,
   pub sx: usize,
 }
-impl<const A: usize> Display for Stack<A> {
+impl Display for Stack {
   fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     let mut string = String::new();
     for i in &self.vec {
@@ -276,53 +438,123 @@ impl<const A: usize> Display for Stack<A> {
     write!(f, "[{}]", string)
   }
 }
-impl<const A: usize> Deref for Stack<A> {
-  type Target = [TypedByte; A];
+impl Deref for Stack {
+  type Target = [TypedByte];
   fn deref(&self) -> &Self::Target {
     &self.vec
   }
 }
-impl<const A: usize> DerefMut for Stack<A> {
+impl DerefMut for Stack {
   fn deref_mut(&mut self) -> &mut Self::Target {
     &mut self.vec
   }
 }
-impl<const A: usize> Stack<A> {
-  pub const fn new() -> Self {
+impl Stack {
+  /// Holds up to `depth` values
+  pub fn new(depth: usize) -> Self {
     Self {
       sx: 0,
-      vec: [TypedByte {
-        value: [0; 4],
-        r#type: Type::Usigned,
-      }; A],
+      vec: vec![TypedByte::default(); depth + 1],
     }
   }
 
-  pub fn top(&self) -> TypedByte {
-    self.vec[self.sx]
+  pub fn top(&self) -> Result<TypedByte, StackError> {
+    if self.sx == 0 {
+      return Err(StackError::Underflow);
+    }
+    Ok(self.vec[self.sx])
   }
-  pub fn pop(&mut self) {
-    self.sx = self.sx.max(1) - 1;
+  pub fn pop(&mut self) -> Result<TypedByte, StackError> {
+    let top = self.top()?;
+    self.sx -= 1;
+    Ok(top)
   }
-  pub fn push(&mut self, a: TypedByte) {
+  pub fn push(&mut self, a: TypedByte) -> Result<(), StackError> {
+    // the slot 0 is never used
+    if self.sx + 1 >= self.vec.len() {
+      return Err(StackError::Overflow);
+    }
     self.sx += 1;
     self.vec[self.sx] = a;
+    Ok(())
   }
 }
 
-const TYPES: [Type; 3] =
-  [Type::Usigned, Type::Signed, Type::Floating];
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StackError {
+  Overflow,
+  Underflow,
+}
+impl Display for StackError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      StackError::Overflow => write!(f, "Stack overflow"),
+      StackError::Underflow => write!(f, "Stack underflow"),
+    }
+  }
+}
+
+/// What integer operations do when the result doesn't fit
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Arithmetic {
+  /// Stop with a runtime error
+  #[default]
+  Trap,
+  Wrapping,
+  Saturating,
+}
+
+/// What happens to memory addresses past the last cell
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum AddressPolicy {
+  /// Wraps around the memory size, like `address % cells`
+  #[default]
+  Wrap,
+  /// Stop with a `MemoryOutOfRange` runtime error
+  Trap,
+}
+
+pub const TYPES: [Type; 6] = [
+  Type::Usigned,
+  Type::Signed,
+  Type::Floating,
+  Type::Usigned64,
+  Type::Signed64,
+  Type::Floating64,
+];
 
 #[derive(Clone, Copy, PartialEq, Debug, Eq, PartialOrd)]
 pub enum Type {
   Usigned = 0,
   Signed = 1,
   Floating = 2,
+  Usigned64 = 3,
+  Signed64 = 4,
+  Floating64 = 5,
 }
 
-impl From<usize> for Type {
-  fn from(value: usize) -> Self {
-    TYPES[value]
+impl Type {
+  /// How `var` declarations and the memory map write it
+  pub const fn name(&self) -> &'static str {
+    match self {
+      Type::Usigned => "u32",
+      Type::Signed => "i32",
+      Type::Floating => "f32",
+      Type::Usigned64 => "u64",
+      Type::Signed64 => "i64",
+      Type::Floating64 => "f64",
+    }
+  }
+  pub fn from_name(name: &str) -> Option<Self> {
+    TYPES.into_iter().find(|a| a.name() == name)
+  }
+}
+
+impl TryFrom<u8> for Type {
+  type Error = u8;
+  /// The type of a nibble, returning the nibble if it has none
+  fn try_from(value: u8) -> Result<Self, u8> {
+    TYPES.get(value as usize).copied().ok_or(value)
   }
 }
 impl Default for Type {
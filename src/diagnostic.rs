@@ -0,0 +1,198 @@
+use std::{fmt::Display, ops::Range};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+  Error,
+  Warning,
+  Note,
+}
+impl Display for Severity {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{}",
+      match self {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+      }
+    )
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticCode {
+  InvalidToken,
+  InvalidOpcode,
+  MissingParam,
+  MalformedParam,
+  UnknownParam,
+  InvalidNumber,
+  UndefinedLabel,
+  DuplicatedLabel,
+  MalformedInclude,
+  MalformedMacro,
+  IncludeFailed,
+  MacroExpression,
+  MalformedRequirement,
+  MalformedConstant,
+  DuplicatedConstant,
+  UndefinedConstant,
+  InvalidExpression,
+  MalformedString,
+  MalformedVariable,
+  DuplicatedVariable,
+  UndefinedVariable,
+}
+impl DiagnosticCode {
+  pub const fn as_str(&self) -> &'static str {
+    match self {
+      DiagnosticCode::InvalidToken => "E0001",
+      DiagnosticCode::InvalidOpcode => "E0002",
+      DiagnosticCode::MissingParam => "E0003",
+      DiagnosticCode::MalformedParam => "E0004",
+      DiagnosticCode::UnknownParam => "E0005",
+      DiagnosticCode::InvalidNumber => "E0006",
+      DiagnosticCode::UndefinedLabel => "E0007",
+      DiagnosticCode::DuplicatedLabel => "E0008",
+      DiagnosticCode::MalformedInclude => "E0009",
+      DiagnosticCode::MalformedMacro => "E0010",
+      DiagnosticCode::IncludeFailed => "E0011",
+      DiagnosticCode::MacroExpression => "E0012",
+      DiagnosticCode::MalformedRequirement => "E0013",
+      DiagnosticCode::MalformedConstant => "E0014",
+      DiagnosticCode::DuplicatedConstant => "E0015",
+      DiagnosticCode::UndefinedConstant => "E0016",
+      DiagnosticCode::InvalidExpression => "E0017",
+      DiagnosticCode::MalformedString => "E0018",
+      DiagnosticCode::MalformedVariable => "E0019",
+      DiagnosticCode::DuplicatedVariable => "E0020",
+      DiagnosticCode::UndefinedVariable => "E0021",
+    }
+  }
+}
+impl Display for DiagnosticCode {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.as_str())
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OriginKind {
+  Include,
+  Macro,
+}
+
+/// The `robsons` line that pulled a file into the compilation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+  pub kind: OriginKind,
+  pub path: String,
+  pub line: usize,
+}
+impl Display for Origin {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{} {}:{}",
+      match self.kind {
+        OriginKind::Include => "included from",
+        OriginKind::Macro => "expanded from macro at",
+      },
+      self.path,
+      self.line
+    )
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+  pub severity: Severity,
+  pub code: DiagnosticCode,
+  pub message: String,
+  pub path: String,
+  /// 1-based line of the offending source
+  pub line: usize,
+  /// 1-based, end exclusive
+  pub columns: Range<usize>,
+  pub source: String,
+  /// Outermost caller first
+  pub chain: Vec<Origin>,
+}
+impl Diagnostic {
+  pub fn error(
+    code: DiagnosticCode,
+    message: String,
+    path: &str,
+    line: usize,
+    source: &str,
+    token: &str,
+  ) -> Self {
+    Self {
+      severity: Severity::Error,
+      code,
+      message,
+      path: path.to_owned(),
+      line,
+      columns: Self::find_columns(source, token),
+      source: source.to_owned(),
+      chain: Vec::new(),
+    }
+  }
+
+  /// Locates `token` inside `source`, falling back to the whole
+  /// non blank part of the line
+  pub fn find_columns(source: &str, token: &str) -> Range<usize> {
+    let found = if token.trim().is_empty() {
+      None
+    } else {
+      source.find(token.trim()).map(|a| (a, token.trim()))
+    };
+    let (start, token) = match found {
+      Some(a) => a,
+      None => {
+        let trimmed = source.trim();
+        (source.len() - source.trim_start().len(), trimmed)
+      }
+    };
+    let start = source[..start].chars().count() + 1;
+    start..start + token.chars().count().max(1)
+  }
+
+  pub fn with_chain(mut self, chain: Vec<Origin>) -> Self {
+    self.chain = chain;
+    self
+  }
+
+  pub fn render(&self) -> String {
+    self.to_string()
+  }
+}
+impl Display for Diagnostic {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let line = self.line.to_string();
+    let pad = " ".repeat(line.len());
+    writeln!(
+      f,
+      "{}[{}]: {}",
+      self.severity, self.code, self.message
+    )?;
+    writeln!(
+      f,
+      "{pad}--> {}:{}:{}",
+      self.path, self.line, self.columns.start
+    )?;
+    writeln!(f, "{pad} |")?;
+    writeln!(f, "{line} | {}", self.source.replace('\t', " "))?;
+    write!(
+      f,
+      "{pad} | {}{}",
+      " ".repeat(self.columns.start.saturating_sub(1)),
+      "^".repeat(self.columns.len().max(1))
+    )?;
+    for origin in self.chain.iter().rev() {
+      write!(f, "\n{pad} = note: {origin}")?;
+    }
+    Ok(())
+  }
+}
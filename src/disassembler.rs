@@ -0,0 +1,150 @@
+use std::collections::BTreeSet;
+
+use crate::{
+  compiler::OPCODE_PARAMS,
+  data_struct::{IError, Type, TypedByte, PARAM_SIZE},
+  interpreter::Interpreter,
+  macros::ierror,
+  utils::{
+    f32_from_bytes, f64_from_bytes, i32_from_bytes, u32_from_bytes,
+  },
+  verifier::JUMP_PARAMS,
+};
+
+/// A decoded param, its value, kind and convert flag
+type Param = (TypedByte, usize, bool);
+
+fn param_to_string(
+  command: usize,
+  param: &Param,
+  labels: &BTreeSet<usize>,
+  is_target: bool,
+) -> Result<String, IError> {
+  let (byte, kind, convert) = *param;
+  let value = *byte;
+  let text = match (kind, byte.r#type) {
+    (0, Type::Usigned)
+      if is_target
+        && labels.contains(&(u32_from_bytes(value) as usize)) =>
+    {
+      format!("lambeu :l{}", u32_from_bytes(value))
+    }
+    (0, Type::Usigned) => format!("comeu {}", u32_from_bytes(value)),
+    (0, Type::Signed) => format!("comeu i{}", i32_from_bytes(value)),
+    (0, Type::Floating) => {
+      let float = f32_from_bytes(value);
+      if !float.is_finite() {
+        return ierror!(
+          "The float {} of command {} has no robson spelling",
+          float,
+          command
+        );
+      }
+      format!("comeu f{float}")
+    }
+    (0, Type::Usigned64 | Type::Signed64) => {
+      format!("comeu {byte}")
+    }
+    (0, Type::Floating64) => {
+      let float = f64_from_bytes(byte.value);
+      if !float.is_finite() {
+        return ierror!(
+          "The float {} of command {} has no robson spelling",
+          float,
+          command
+        );
+      }
+      format!("comeu {byte}")
+    }
+    (kind, Type::Usigned) => format!(
+      "{} {}",
+      match kind {
+        1 => "chupou",
+        2 => "fudeu",
+        _ => "penetrou",
+      },
+      u32_from_bytes(value)
+    ),
+    (_, r#type) => {
+      return ierror!(
+        "Command {} has an address param typed as {:?}",
+        command,
+        r#type
+      )
+    }
+  };
+  if convert {
+    Ok(format!("{text} robson"))
+  } else {
+    Ok(text)
+  }
+}
+
+/// Turns a compiled buffer back into robson source that compiles
+/// to the same bytes
+pub fn disassemble(buffer: &[u8]) -> Result<String, IError> {
+  let instructions =
+    Interpreter::decode(buffer).map_err(IError::message)?;
+  let count = instructions.len();
+  let mut labels = BTreeSet::new();
+
+  for (command, instruction) in instructions.iter().enumerate() {
+    let opcode = instruction.opcode;
+    if opcode == 0 || opcode as usize >= OPCODE_PARAMS.len() {
+      return ierror!(
+        "Invalid opcode {} at command {}",
+        opcode,
+        command
+      );
+    }
+    for (jump, index) in JUMP_PARAMS {
+      let (byte, kind, _) = instruction.params[index];
+      if opcode == jump
+        && kind == 0
+        && byte.r#type == Type::Usigned
+        && u32_from_bytes(*byte) as usize <= count
+      {
+        labels.insert(u32_from_bytes(*byte) as usize);
+      }
+    }
+  }
+
+  let mut source = String::new();
+  for (command, instruction) in instructions.iter().enumerate() {
+    let opcode = instruction.opcode;
+    if labels.contains(&command) {
+      source.push_str(&format!("l{command}:\n"));
+    }
+    let mut opcode_line = vec!["robson"; opcode as usize].join(" ");
+    opcode_line.push('\n');
+    source.push_str(&opcode_line);
+
+    let param_count = OPCODE_PARAMS[opcode as usize] as usize;
+    for (index, param) in instruction.params.iter().enumerate() {
+      if index >= param_count {
+        let (byte, kind, convert) = *param;
+        if byte.value != [0; PARAM_SIZE]
+          || kind != 0
+          || convert
+          || byte.r#type != Type::Usigned
+        {
+          return ierror!(
+            "Unused param {} of command {command} is not empty",
+            index + 1
+          );
+        }
+        continue;
+      }
+      let is_target = JUMP_PARAMS.contains(&(opcode, index));
+      source.push_str(&param_to_string(
+        command, param, &labels, is_target,
+      )?);
+      source.push('\n');
+    }
+    source.push('\n');
+  }
+  if labels.contains(&count) {
+    source.push_str(&format!("l{count}:\n"));
+  }
+  Ok(source)
+}
@@ -0,0 +1,463 @@
+use std::{collections::HashMap, fmt::Display};
+
+use crate::{
+  data_struct::{Type, TypedByte},
+  parser::unescape,
+  utils::char_to_u32,
+};
+
+/// A value evaluated while compiling, over numbers, `:labels`, the
+/// `const` names and `&var` addresses of the file, which are replaced
+/// when parsing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+  Number(TypedByte),
+  Label(String),
+  Neg(Box<Expr>),
+  Binary(Operator, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+  Add,
+  Sub,
+  Mul,
+  Div,
+  Mod,
+}
+impl Operator {
+  fn from_char(char: char) -> Option<Self> {
+    Some(match char {
+      '+' => Self::Add,
+      '-' => Self::Sub,
+      '*' => Self::Mul,
+      '/' => Self::Div,
+      '%' => Self::Mod,
+      _ => return None,
+    })
+  }
+  fn as_char(self) -> char {
+    match self {
+      Self::Add => '+',
+      Self::Sub => '-',
+      Self::Mul => '*',
+      Self::Div => '/',
+      Self::Mod => '%',
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExprError {
+  UndefinedLabel(String),
+  UndefinedConstant(String),
+  UndefinedVariable(String),
+  /// The word and why it isnt a number
+  InvalidNumber(String, String),
+  Invalid(String),
+}
+impl Display for ExprError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ExprError::UndefinedLabel(name) => {
+        write!(f, "Cant find label '{name}'")
+      }
+      ExprError::UndefinedConstant(name) => {
+        write!(f, "Cant find constant '{name}'")
+      }
+      ExprError::UndefinedVariable(name) => write!(
+        f,
+        "Cant find variable '{name}', it must be declared before"
+      ),
+      ExprError::InvalidNumber(word, err) => {
+        write!(f, "Invalid number '{word}': {err}")
+      }
+      ExprError::Invalid(message) => write!(f, "{message}"),
+    }
+  }
+}
+
+pub const OPERATORS: [char; 7] = ['+', '-', '*', '/', '%', '(', ')'];
+
+/// A `comeu` value, `u64:`, `i64:`, `f64:`, `i` and `f` pick the type
+/// and a quoted char is its UTF-8 bytes
+pub fn literal(text: &str) -> Result<TypedByte, String> {
+  let err = |err: &dyn Display| err.to_string();
+  if let Some(inner) =
+    text.strip_prefix('\'').and_then(|a| a.strip_suffix('\''))
+  {
+    let inner = unescape(inner)?;
+    let mut chars = inner.chars();
+    return match (chars.next(), chars.next()) {
+      (Some(char), None) => Ok(char_to_u32(char).into()),
+      _ => Err(format!("expected a single char, found '{inner}'")),
+    };
+  }
+  if let Some((prefix @ ("u64" | "i64" | "f64"), value)) =
+    text.split_once(':')
+  {
+    return Ok(match prefix {
+      "u64" => value.parse::<u64>().map_err(|a| err(&a))?.into(),
+      "i64" => value.parse::<i64>().map_err(|a| err(&a))?.into(),
+      _ => value.parse::<f64>().map_err(|a| err(&a))?.into(),
+    });
+  }
+  if let Some(value) = text.strip_prefix('f') {
+    Ok(value.parse::<f32>().map_err(|a| err(&a))?.into())
+  } else if let Some(value) = text.strip_prefix('i') {
+    Ok(value.parse::<i32>().map_err(|a| err(&a))?.into())
+  } else {
+    Ok(text.parse::<u32>().map_err(|a| err(&a))?.into())
+  }
+}
+
+/// Whether `name` can be given to a `const`, numbers like `i5` cant
+pub fn is_name(name: &str) -> bool {
+  let mut chars = name.chars();
+  chars
+    .next()
+    .is_some_and(|a| a.is_ascii_alphabetic() || a == '_')
+    && chars.all(|a| a.is_ascii_alphanumeric() || a == '_')
+    && name != "robson"
+    && literal(name).is_err()
+}
+
+/// The type of an operation, floats win over integers, 64-bit over
+/// 32-bit and signed over unsigned
+pub fn promote(a: Type, b: Type) -> Type {
+  let float =
+    |a: Type| matches!(a, Type::Floating | Type::Floating64);
+  let signed = |a: Type| matches!(a, Type::Signed | Type::Signed64);
+  let wide = a >= Type::Usigned64 || b >= Type::Usigned64;
+  match (float(a) || float(b), signed(a) || signed(b), wide) {
+    (true, _, false) => Type::Floating,
+    (true, _, true) => Type::Floating64,
+    (false, false, false) => Type::Usigned,
+    (false, true, false) => Type::Signed,
+    (false, false, true) => Type::Usigned64,
+    (false, true, true) => Type::Signed64,
+  }
+}
+
+/// `value` as `r#type`, `None` if it doesnt fit
+fn from_integer(value: i128, r#type: Type) -> Option<TypedByte> {
+  match r#type {
+    Type::Usigned => u32::try_from(value).ok().map(Into::into),
+    Type::Signed => i32::try_from(value).ok().map(Into::into),
+    Type::Usigned64 => u64::try_from(value).ok().map(Into::into),
+    Type::Signed64 => i64::try_from(value).ok().map(Into::into),
+    Type::Floating => Some((value as f32).into()),
+    Type::Floating64 => Some((value as f64).into()),
+  }
+}
+
+impl Expr {
+  pub fn parse(
+    text: &str,
+    constants: &HashMap<String, Expr>,
+  ) -> Result<Expr, ExprError> {
+    // a whole literal, `i-5` isnt `i - 5`
+    if let Ok(value) = literal(text.trim()) {
+      return Ok(Expr::Number(value));
+    }
+    let mut parser = Parser {
+      lexemes: lex(text),
+      pos: 0,
+      constants,
+    };
+    let expr = parser.sum()?;
+    match parser.lexemes.get(parser.pos) {
+      Some(lexeme) => Err(ExprError::Invalid(format!(
+        "Unexpected '{lexeme}' in expression"
+      ))),
+      None => Ok(expr),
+    }
+  }
+
+  /// Calls `f` with the name of every label
+  pub fn labels_mut(&mut self, f: &mut dyn FnMut(&mut String)) {
+    match self {
+      Expr::Number(_) => {}
+      Expr::Label(name) => f(name),
+      Expr::Neg(expr) => expr.labels_mut(f),
+      Expr::Binary(_, a, b) => {
+        a.labels_mut(f);
+        b.labels_mut(f);
+      }
+    }
+  }
+  pub fn has_label(&self, f: &dyn Fn(&str) -> bool) -> bool {
+    match self {
+      Expr::Number(_) => false,
+      Expr::Label(name) => f(name),
+      Expr::Neg(expr) => expr.has_label(f),
+      Expr::Binary(_, a, b) => a.has_label(f) || b.has_label(f),
+    }
+  }
+  /// Replaces the labels `label` knows by their value
+  pub fn resolve(&mut self, label: &dyn Fn(&str) -> Option<u32>) {
+    match self {
+      Expr::Number(_) => {}
+      Expr::Label(name) => {
+        if let Some(value) = label(name) {
+          *self = Expr::Number(value.into());
+        }
+      }
+      Expr::Neg(expr) => expr.resolve(label),
+      Expr::Binary(_, a, b) => {
+        a.resolve(label);
+        b.resolve(label);
+      }
+    }
+  }
+
+  /// Known without evaluating, labels are unsigned
+  pub fn r#type(&self) -> Type {
+    match self {
+      Expr::Number(value) => value.r#type,
+      Expr::Label(_) => Type::Usigned,
+      Expr::Neg(expr) => match expr.r#type() {
+        Type::Usigned => Type::Signed,
+        Type::Usigned64 => Type::Signed64,
+        r#type => r#type,
+      },
+      Expr::Binary(_, a, b) => promote(a.r#type(), b.r#type()),
+    }
+  }
+
+  pub fn eval(
+    &self,
+    label: &dyn Fn(&str) -> Option<u32>,
+  ) -> Result<TypedByte, ExprError> {
+    let r#type = self.r#type();
+    let overflow = || {
+      ExprError::Invalid(format!(
+        "Expression overflows the type {:?}",
+        r#type
+      ))
+    };
+    match self {
+      Expr::Number(value) => Ok(*value),
+      Expr::Label(name) => label(name)
+        .map(Into::into)
+        .ok_or_else(|| ExprError::UndefinedLabel(name.clone())),
+      Expr::Neg(expr) => {
+        let value = expr.eval(label)?;
+        match value.integer() {
+          Some(a) => from_integer(-a, r#type).ok_or_else(overflow),
+          None => {
+            let mut value: TypedByte = (-value.float()).into();
+            value.convert(r#type);
+            Ok(value)
+          }
+        }
+      }
+      Expr::Binary(operator, a, b) => {
+        let (a, b) = (a.eval(label)?, b.eval(label)?);
+        if let (Some(a), Some(b), false) = (
+          a.integer(),
+          b.integer(),
+          matches!(r#type, Type::Floating | Type::Floating64),
+        ) {
+          let value = match operator {
+            Operator::Add => a.checked_add(b),
+            Operator::Sub => a.checked_sub(b),
+            Operator::Mul => a.checked_mul(b),
+            Operator::Div | Operator::Mod if b == 0 => {
+              return Err(ExprError::Invalid(
+                "Division by zero in expression".to_owned(),
+              ));
+            }
+            Operator::Div => a.checked_div(b),
+            Operator::Mod => a.checked_rem(b),
+          };
+          return value
+            .and_then(|a| from_integer(a, r#type))
+            .ok_or_else(overflow);
+        }
+        let (a, b) = (a.float(), b.float());
+        let mut value: TypedByte = match operator {
+          Operator::Add => a + b,
+          Operator::Sub => a - b,
+          Operator::Mul => a * b,
+          Operator::Div => a / b,
+          Operator::Mod => a % b,
+        }
+        .into();
+        value.convert(r#type);
+        Ok(value)
+      }
+    }
+  }
+}
+
+/// Text that `Expr::parse` reads back, with the constants already
+/// replaced
+impl Display for Expr {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    let nested =
+      |f: &mut std::fmt::Formatter<'_>, expr: &Expr| match expr {
+        Expr::Binary(..) => write!(f, "({expr})"),
+        _ => write!(f, "{expr}"),
+      };
+    match self {
+      Expr::Number(value) => {
+        let r#type = value.r#type;
+        // the lexer splits `i-5` at the `-`, so negatives are negated
+        // and `-MIN` doesnt fit its type
+        match value.integer() {
+          Some(a) if a < 0 => {
+            let mut abs: TypedByte = ((-a - 1) as u64).into();
+            let mut one: TypedByte = 1u32.into();
+            abs.convert(r#type);
+            one.convert(r#type);
+            write!(f, "(-{abs} - {one})")
+          }
+          None if value.float().is_sign_negative() => {
+            let mut abs: TypedByte = (-value.float()).into();
+            abs.convert(r#type);
+            write!(f, "(-{abs})")
+          }
+          _ => write!(f, "{value}"),
+        }
+      }
+      Expr::Label(name) => write!(f, ":{name}"),
+      Expr::Neg(expr) => {
+        write!(f, "-")?;
+        nested(f, expr)
+      }
+      Expr::Binary(operator, a, b) => {
+        nested(f, a)?;
+        write!(f, " {} ", operator.as_char())?;
+        nested(f, b)
+      }
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Lexeme {
+  Word(String),
+  Operator(char),
+}
+impl Display for Lexeme {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Lexeme::Word(word) => write!(f, "{word}"),
+      Lexeme::Operator(char) => write!(f, "{char}"),
+    }
+  }
+}
+
+fn lex(text: &str) -> Vec<Lexeme> {
+  let mut lexemes = Vec::new();
+  let mut word = String::new();
+  let mut chars = text.chars();
+  while let Some(char) = chars.next() {
+    if char == '\'' && word.is_empty() {
+      // a char literal, `'+'` and `' '` included
+      word.push(char);
+      while let Some(next) = chars.next() {
+        word.push(next);
+        match next {
+          '\\' => word.extend(chars.next()),
+          '\'' => break,
+          _ => {}
+        }
+      }
+    } else if char.is_whitespace() || OPERATORS.contains(&char) {
+      if !word.is_empty() {
+        lexemes.push(Lexeme::Word(std::mem::take(&mut word)));
+      }
+      if !char.is_whitespace() {
+        lexemes.push(Lexeme::Operator(char));
+      }
+    } else {
+      word.push(char);
+    }
+  }
+  if !word.is_empty() {
+    lexemes.push(Lexeme::Word(word));
+  }
+  lexemes
+}
+
+struct Parser<'a> {
+  lexemes: Vec<Lexeme>,
+  pos: usize,
+  constants: &'a HashMap<String, Expr>,
+}
+impl Parser<'_> {
+  fn next(&mut self) -> Option<Lexeme> {
+    let lexeme = self.lexemes.get(self.pos).cloned();
+    self.pos += 1;
+    lexeme
+  }
+  fn operator(&mut self, operators: &[char]) -> Option<Operator> {
+    match self.lexemes.get(self.pos) {
+      Some(Lexeme::Operator(a)) if operators.contains(a) => {
+        self.pos += 1;
+        Operator::from_char(*a)
+      }
+      _ => None,
+    }
+  }
+
+  fn sum(&mut self) -> Result<Expr, ExprError> {
+    let mut expr = self.product()?;
+    while let Some(operator) = self.operator(&['+', '-']) {
+      let right = self.product()?;
+      expr = Expr::Binary(operator, Box::new(expr), Box::new(right));
+    }
+    Ok(expr)
+  }
+  fn product(&mut self) -> Result<Expr, ExprError> {
+    let mut expr = self.unary()?;
+    while let Some(operator) = self.operator(&['*', '/', '%']) {
+      let right = self.unary()?;
+      expr = Expr::Binary(operator, Box::new(expr), Box::new(right));
+    }
+    Ok(expr)
+  }
+  fn unary(&mut self) -> Result<Expr, ExprError> {
+    if self.operator(&['-']).is_some() {
+      return Ok(Expr::Neg(Box::new(self.unary()?)));
+    }
+    match self.next() {
+      Some(Lexeme::Operator('(')) => {
+        let expr = self.sum()?;
+        match self.next() {
+          Some(Lexeme::Operator(')')) => Ok(expr),
+          _ => Err(ExprError::Invalid(
+            "Expected ')' in expression".to_owned(),
+          )),
+        }
+      }
+      Some(Lexeme::Word(word)) => self.word(word),
+      Some(lexeme) => Err(ExprError::Invalid(format!(
+        "Expected a value, found '{lexeme}'"
+      ))),
+      None => Err(ExprError::Invalid(
+        "Expected a value at the end of the expression".to_owned(),
+      )),
+    }
+  }
+  fn word(&self, word: String) -> Result<Expr, ExprError> {
+    if let Some(name) = word.strip_prefix(':') {
+      return Ok(Expr::Label(name.to_owned()));
+    }
+    if let Some(expr) = self.constants.get(&word) {
+      return Ok(expr.clone());
+    }
+    if let Some(name) = word.strip_prefix('&') {
+      return Err(ExprError::UndefinedVariable(name.to_owned()));
+    }
+    match literal(&word) {
+      Ok(value) => Ok(Expr::Number(value)),
+      Err(_) if is_name(&word) => {
+        Err(ExprError::UndefinedConstant(word))
+      }
+      Err(err) => Err(ExprError::InvalidNumber(word, err)),
+    }
+  }
+}
@@ -1,4 +1,5 @@
 use std::{
+  cmp::Ordering,
   io::Write,
   time::{Duration, Instant},
 };
@@ -6,121 +7,579 @@ use std::{
 use rand::Rng;
 
 use crate::{
-  data_struct::{IError, Instruction, Stack, Type, TypedByte},
-  macros::{convert, force_u32, someierror, top, try_err},
+  container::{Container, Reader},
+  data_struct::{
+    AddressPolicy, Arithmetic, IError, Instruction, Record, Records,
+    Stack, Type, TypedByte, PARAM_SIZE,
+  },
+  macros::{
+    cell, convert, force_u32, ierror, pop, push, runtime_error,
+    stack_error, top, try_err,
+  },
+  runtime_error::{ErrorContext, RuntimeError, STACK_VIEW},
+  source_map::{SourceLocation, SourceMap},
   utils::{
-    convert_kind_byte, convert_two_bits, f32_from_bytes, f32_mod,
+    char_to_u32, convert_kind_byte, convert_two_bits,
+    convert_types_bytes, crc32, f32_from_bytes, f32_mod,
     f32_to_bytes, i32_from_bytes, i32_mod, i32_to_bytes,
     u32_from_bytes, u32_mod, u32_to_bytes,
   },
+  verifier::{verify, Problem, ProblemKind},
   Infra,
 };
 
 use super::utils::{
-  approx_equal, f32_add, f32_div, f32_mul, f32_sub, i32_add, i32_div,
-  i32_mul, i32_sub, u32_add, u32_div, u32_mul, u32_sub,
+  approx_equal, approx_equal_f64, f32_add, f32_div, f32_mul, f32_sub,
+  f64_add, f64_div, f64_from_bytes, f64_mod, f64_mul, f64_sub,
+  f64_to_bytes, i32_add, i32_div, i32_mul, i32_shr, i32_sub, i64_add,
+  i64_div, i64_from_bytes, i64_mod, i64_mul, i64_shr, i64_sub,
+  i64_to_bytes, u32_add, u32_div, u32_mul, u32_shl, u32_shr, u32_sub,
+  u64_add, u64_div, u64_from_bytes, u64_mod, u64_mul, u64_shl,
+  u64_shr, u64_sub, u64_to_bytes, widen,
 };
 
-pub const OPERATIONS: [[fn(param1: &mut TypedByte, param2: [u8; 4]);
-  3]; 5] = [
+pub type Operation = fn(
+  param1: &mut TypedByte,
+  param2: TypedByte,
+  mode: Arithmetic,
+) -> bool;
+
+#[inline]
+fn store(param: &mut TypedByte, value: Option<[u8; 4]>) -> bool {
+  store_wide(param, value.map(widen))
+}
+
+#[inline]
+fn store_wide(param: &mut TypedByte, value: Option<[u8; 8]>) -> bool {
+  match value {
+    Some(value) => {
+      param.value = value;
+      true
+    }
+    None => false,
+  }
+}
+
+#[inline]
+fn bits(param: &mut TypedByte, value: u32) -> bool {
+  param.value = widen(u32_to_bytes(value));
+  true
+}
+
+#[inline]
+fn bits_wide(param: &mut TypedByte, value: u64) -> bool {
+  param.value = u64_to_bytes(value);
+  true
+}
+
+/// Indexed by kind then type, false when the result doesn't fit and
+/// `None` when the kind doesn't apply to the type
+///
+/// Kinds: add, sub, mul, div, mod, and, or, xor, not, shift left,
+/// logical shift right and arithmetic shift right
+pub const OPERATIONS: [[Option<Operation>; 6]; 12] = [
   [
-    |v1, v2| v1.value = u32_to_bytes(u32_add(**v1, v2)),
-    |v1, v2| v1.value = i32_to_bytes(i32_add(**v1, v2)),
-    |v1, v2| v1.value = f32_to_bytes(f32_add(**v1, v2)),
+    Some(|v1, v2, mode| {
+      store(v1, u32_add(**v1, *v2, mode).map(u32_to_bytes))
+    }),
+    Some(|v1, v2, mode| {
+      store(v1, i32_add(**v1, *v2, mode).map(i32_to_bytes))
+    }),
+    Some(|v1, v2, _| {
+      v1.value = widen(f32_to_bytes(f32_add(**v1, *v2)));
+      true
+    }),
+    Some(|v1, v2, mode| {
+      store_wide(
+        v1,
+        u64_add(v1.value, v2.value, mode).map(u64_to_bytes),
+      )
+    }),
+    Some(|v1, v2, mode| {
+      store_wide(
+        v1,
+        i64_add(v1.value, v2.value, mode).map(i64_to_bytes),
+      )
+    }),
+    Some(|v1, v2, _| {
+      v1.value = f64_to_bytes(f64_add(v1.value, v2.value));
+      true
+    }),
   ],
   [
-    |v1, v2| {
-      v1.value = u32_to_bytes({
-        if **v1 >= v2 {
-          u32_sub(**v1, v2)
-        } else {
-          0u32
-        }
-      })
-    },
-    |v1, v2| v1.value = i32_to_bytes(i32_sub(**v1, v2)),
-    |v1, v2| v1.value = f32_to_bytes(f32_sub(**v1, v2)),
+    Some(|v1, v2, mode| {
+      store(v1, u32_sub(**v1, *v2, mode).map(u32_to_bytes))
+    }),
+    Some(|v1, v2, mode| {
+      store(v1, i32_sub(**v1, *v2, mode).map(i32_to_bytes))
+    }),
+    Some(|v1, v2, _| {
+      v1.value = widen(f32_to_bytes(f32_sub(**v1, *v2)));
+      true
+    }),
+    Some(|v1, v2, mode| {
+      store_wide(
+        v1,
+        u64_sub(v1.value, v2.value, mode).map(u64_to_bytes),
+      )
+    }),
+    Some(|v1, v2, mode| {
+      store_wide(
+        v1,
+        i64_sub(v1.value, v2.value, mode).map(i64_to_bytes),
+      )
+    }),
+    Some(|v1, v2, _| {
+      v1.value = f64_to_bytes(f64_sub(v1.value, v2.value));
+      true
+    }),
+  ],
+  [
+    Some(|v1, v2, mode| {
+      store(v1, u32_mul(**v1, *v2, mode).map(u32_to_bytes))
+    }),
+    Some(|v1, v2, mode| {
+      store(v1, i32_mul(**v1, *v2, mode).map(i32_to_bytes))
+    }),
+    Some(|v1, v2, _| {
+      v1.value = widen(f32_to_bytes(f32_mul(**v1, *v2)));
+      true
+    }),
+    Some(|v1, v2, mode| {
+      store_wide(
+        v1,
+        u64_mul(v1.value, v2.value, mode).map(u64_to_bytes),
+      )
+    }),
+    Some(|v1, v2, mode| {
+      store_wide(
+        v1,
+        i64_mul(v1.value, v2.value, mode).map(i64_to_bytes),
+      )
+    }),
+    Some(|v1, v2, _| {
+      v1.value = f64_to_bytes(f64_mul(v1.value, v2.value));
+      true
+    }),
+  ],
+  [
+    Some(|v1, v2, mode| {
+      store(v1, u32_div(**v1, *v2, mode).map(u32_to_bytes))
+    }),
+    Some(|v1, v2, mode| {
+      store(v1, i32_div(**v1, *v2, mode).map(i32_to_bytes))
+    }),
+    Some(|v1, v2, _| {
+      v1.value = widen(f32_to_bytes(f32_div(**v1, *v2)));
+      true
+    }),
+    Some(|v1, v2, mode| {
+      store_wide(
+        v1,
+        u64_div(v1.value, v2.value, mode).map(u64_to_bytes),
+      )
+    }),
+    Some(|v1, v2, mode| {
+      store_wide(
+        v1,
+        i64_div(v1.value, v2.value, mode).map(i64_to_bytes),
+      )
+    }),
+    Some(|v1, v2, _| {
+      v1.value = f64_to_bytes(f64_div(v1.value, v2.value));
+      true
+    }),
+  ],
+  [
+    Some(|v1, v2, mode| {
+      store(v1, u32_mod(**v1, *v2, mode).map(u32_to_bytes))
+    }),
+    Some(|v1, v2, mode| {
+      store(v1, i32_mod(**v1, *v2, mode).map(i32_to_bytes))
+    }),
+    Some(|v1, v2, _| {
+      v1.value = widen(f32_to_bytes(f32_mod(**v1, *v2)));
+      true
+    }),
+    Some(|v1, v2, mode| {
+      store_wide(
+        v1,
+        u64_mod(v1.value, v2.value, mode).map(u64_to_bytes),
+      )
+    }),
+    Some(|v1, v2, mode| {
+      store_wide(
+        v1,
+        i64_mod(v1.value, v2.value, mode).map(i64_to_bytes),
+      )
+    }),
+    Some(|v1, v2, _| {
+      v1.value = f64_to_bytes(f64_mod(v1.value, v2.value));
+      true
+    }),
+  ],
+  [
+    Some(|v1, v2, _| {
+      bits(v1, u32_from_bytes(**v1) & u32_from_bytes(*v2))
+    }),
+    Some(|v1, v2, _| {
+      bits(v1, u32_from_bytes(**v1) & u32_from_bytes(*v2))
+    }),
+    None,
+    Some(|v1, v2, _| {
+      bits_wide(
+        v1,
+        u64_from_bytes(v1.value) & u64_from_bytes(v2.value),
+      )
+    }),
+    Some(|v1, v2, _| {
+      bits_wide(
+        v1,
+        u64_from_bytes(v1.value) & u64_from_bytes(v2.value),
+      )
+    }),
+    None,
+  ],
+  [
+    Some(|v1, v2, _| {
+      bits(v1, u32_from_bytes(**v1) | u32_from_bytes(*v2))
+    }),
+    Some(|v1, v2, _| {
+      bits(v1, u32_from_bytes(**v1) | u32_from_bytes(*v2))
+    }),
+    None,
+    Some(|v1, v2, _| {
+      bits_wide(
+        v1,
+        u64_from_bytes(v1.value) | u64_from_bytes(v2.value),
+      )
+    }),
+    Some(|v1, v2, _| {
+      bits_wide(
+        v1,
+        u64_from_bytes(v1.value) | u64_from_bytes(v2.value),
+      )
+    }),
+    None,
+  ],
+  [
+    Some(|v1, v2, _| {
+      bits(v1, u32_from_bytes(**v1) ^ u32_from_bytes(*v2))
+    }),
+    Some(|v1, v2, _| {
+      bits(v1, u32_from_bytes(**v1) ^ u32_from_bytes(*v2))
+    }),
+    None,
+    Some(|v1, v2, _| {
+      bits_wide(
+        v1,
+        u64_from_bytes(v1.value) ^ u64_from_bytes(v2.value),
+      )
+    }),
+    Some(|v1, v2, _| {
+      bits_wide(
+        v1,
+        u64_from_bytes(v1.value) ^ u64_from_bytes(v2.value),
+      )
+    }),
+    None,
+  ],
+  [
+    Some(|v1, _, _| bits(v1, !u32_from_bytes(**v1))),
+    Some(|v1, _, _| bits(v1, !u32_from_bytes(**v1))),
+    None,
+    Some(|v1, _, _| bits_wide(v1, !u64_from_bytes(v1.value))),
+    Some(|v1, _, _| bits_wide(v1, !u64_from_bytes(v1.value))),
+    None,
   ],
   [
-    |v1, v2| v1.value = u32_to_bytes(u32_mul(**v1, v2)),
-    |v1, v2| v1.value = i32_to_bytes(i32_mul(**v1, v2)),
-    |v1, v2| v1.value = f32_to_bytes(f32_mul(**v1, v2)),
+    Some(|v1, v2, mode| {
+      store(v1, u32_shl(**v1, *v2, mode).map(u32_to_bytes))
+    }),
+    Some(|v1, v2, mode| {
+      store(v1, u32_shl(**v1, *v2, mode).map(u32_to_bytes))
+    }),
+    None,
+    Some(|v1, v2, mode| {
+      store_wide(
+        v1,
+        u64_shl(v1.value, v2.value, mode).map(u64_to_bytes),
+      )
+    }),
+    Some(|v1, v2, mode| {
+      store_wide(
+        v1,
+        u64_shl(v1.value, v2.value, mode).map(u64_to_bytes),
+      )
+    }),
+    None,
   ],
   [
-    |v1, v2| v1.value = u32_to_bytes(u32_div(**v1, v2)),
-    |v1, v2| v1.value = i32_to_bytes(i32_div(**v1, v2)),
-    |v1, v2| v1.value = f32_to_bytes(f32_div(**v1, v2)),
+    Some(|v1, v2, mode| {
+      store(v1, u32_shr(**v1, *v2, mode).map(u32_to_bytes))
+    }),
+    Some(|v1, v2, mode| {
+      store(v1, u32_shr(**v1, *v2, mode).map(u32_to_bytes))
+    }),
+    None,
+    Some(|v1, v2, mode| {
+      store_wide(
+        v1,
+        u64_shr(v1.value, v2.value, mode).map(u64_to_bytes),
+      )
+    }),
+    Some(|v1, v2, mode| {
+      store_wide(
+        v1,
+        u64_shr(v1.value, v2.value, mode).map(u64_to_bytes),
+      )
+    }),
+    None,
   ],
+  // the sign of unsigned values is always 0
   [
-    |v1, v2| v1.value = u32_to_bytes(u32_mod(**v1, v2)),
-    |v1, v2| v1.value = i32_to_bytes(i32_mod(**v1, v2)),
-    |v1, v2| v1.value = f32_to_bytes(f32_mod(**v1, v2)),
+    Some(|v1, v2, mode| {
+      store(v1, u32_shr(**v1, *v2, mode).map(u32_to_bytes))
+    }),
+    Some(|v1, v2, mode| {
+      store(v1, i32_shr(**v1, *v2, mode).map(i32_to_bytes))
+    }),
+    None,
+    Some(|v1, v2, mode| {
+      store_wide(
+        v1,
+        u64_shr(v1.value, v2.value, mode).map(u64_to_bytes),
+      )
+    }),
+    Some(|v1, v2, mode| {
+      store_wide(
+        v1,
+        i64_shr(v1.value, v2.value, mode).map(i64_to_bytes),
+      )
+    }),
+    None,
   ],
 ];
 
-pub struct Interpreter<'a, const A: usize> {
-  pub memory: [TypedByte; A],
+/// Where `run_for` and `resume` stopped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecutionState {
+  /// The instruction budget ran out, the program can go on
+  Running,
+  Halted,
+  /// The next instruction is an input, call `provide_input`
+  WaitingForInput,
+  Errored(RuntimeError),
+}
+
+pub const DEFAULT_MEMORY_CELLS: usize = 1024;
+pub const DEFAULT_STACK_DEPTH: usize = 65534;
+pub const DEFAULT_CALL_DEPTH: usize = 1024;
+
+/// Sizes and policies of an `Interpreter`, its memory and stack live
+/// on the heap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InterpreterBuilder {
+  memory_cells: usize,
+  max_memory_cells: usize,
+  stack_depth: usize,
+  call_depth: usize,
+  address_policy: AddressPolicy,
+}
+impl Default for InterpreterBuilder {
+  fn default() -> Self {
+    Self {
+      memory_cells: DEFAULT_MEMORY_CELLS,
+      max_memory_cells: 0,
+      stack_depth: DEFAULT_STACK_DEPTH,
+      call_depth: DEFAULT_CALL_DEPTH,
+      address_policy: AddressPolicy::Wrap,
+    }
+  }
+}
+impl InterpreterBuilder {
+  /// Cells allocated up front
+  pub fn memory_cells(mut self, cells: usize) -> Self {
+    self.memory_cells = cells;
+    self
+  }
+  /// Lets the memory grow on demand up to `cells`, addresses past it
+  /// follow the address policy
+  pub fn max_memory_cells(mut self, cells: usize) -> Self {
+    self.max_memory_cells = cells;
+    self
+  }
+  pub fn stack_depth(mut self, depth: usize) -> Self {
+    self.stack_depth = depth;
+    self
+  }
+  /// How many calls can be nested before returning
+  pub fn call_depth(mut self, depth: usize) -> Self {
+    self.call_depth = depth;
+    self
+  }
+  pub fn address_policy(mut self, policy: AddressPolicy) -> Self {
+    self.address_policy = policy;
+    self
+  }
+  fn memory_limit(&self) -> usize {
+    self.memory_cells.max(self.max_memory_cells)
+  }
+
+  pub fn build(self, buffer: &[u8]) -> Interpreter<'_> {
+    Interpreter::with_sizes(buffer, &self)
+  }
+
+  /// Like `build`, for a container written by
+  /// `Compiler::write_container`
+  pub fn load(
+    self,
+    container: &[u8],
+  ) -> Result<Interpreter<'_>, IError> {
+    let container = Container::load(container)?;
+    let memory = container.memory as usize;
+    if memory > self.memory_limit() {
+      return Err(IError::message(format!(
+        "'{}' requires {} memory cells, but only {} are available",
+        container.source,
+        container.memory,
+        self.memory_limit()
+      )));
+    }
+    let mut interpreter = self
+      .memory_cells(self.memory_cells.max(memory))
+      .build(container.code);
+    if let Some(problem) = interpreter.invalid {
+      return Err(IError::message(problem));
+    }
+    interpreter.set_symbols(container.symbols);
+    if let Some(debug) = container.debug {
+      interpreter.set_source_map(SourceMap::from_bytes(debug)?);
+    }
+    Ok(interpreter)
+  }
+
+  /// Like `build`, continuing from `Interpreter::snapshot`
+  pub fn restore<'a>(
+    self,
+    buffer: &'a [u8],
+    snapshot: &[u8],
+  ) -> Result<Interpreter<'a>, IError> {
+    Interpreter::restore_with(buffer, snapshot, &self)
+  }
+}
+
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"RBSS";
+pub const SNAPSHOT_VERSION: u16 = 4;
+
+/// How many instructions run between two deadline checks
+pub const DEADLINE_INTERVAL: u64 = 1024;
+
+pub struct Interpreter<'a> {
+  pub memory: Vec<TypedByte>,
   pub debug: bool,
-  pub stack: Stack<65535>,
-  convertions: [fn(&mut TypedByte, &mut Interpreter<A>) -> bool; 4],
-  operations: [fn(&mut Interpreter<A>, &mut dyn Infra); 256],
+  pub stack: Stack,
+  /// Return addresses of the `call` opcode
+  calls: Vec<usize>,
+  call_depth: usize,
+  max_memory: usize,
+  address_policy: AddressPolicy,
+  convertions: [fn(&mut TypedByte, &mut Interpreter) -> bool; 4],
+  operations: [fn(&mut Interpreter, &mut dyn Infra); 256],
   time: Option<Instant>,
   duration: Option<Duration>,
   index: usize,
   instruction: Instruction,
   buffer: &'a [u8],
-  err: Option<IError>,
+  /// `buffer` decoded when built
+  instructions: Vec<Instruction>,
+  /// The first record that failed to decode, reported by `step`
+  invalid: Option<Problem>,
+  err: Option<RuntimeError>,
+  command: usize,
+  arithmetic: Arithmetic,
+  require_verified: bool,
+  verified: bool,
+  breakpoints: Vec<usize>,
+  /// `executed` when `continue_until_break` last stopped
+  stopped_at: Option<u64>,
+  symbols: Vec<(String, u32)>,
+  source_map: Option<SourceMap>,
+  fuel: Option<u64>,
+  fuel_costs: [u64; 256],
+  deadline: Option<Instant>,
+  executed: u64,
+  suspend_on_input: bool,
+  input: Option<String>,
+  waiting_input: bool,
 }
 #[inline]
-fn not_convert<const A: usize>(
+fn not_convert(
   _: &mut TypedByte,
-  _interpreter: &mut Interpreter<A>,
+  _interpreter: &mut Interpreter,
 ) -> bool {
   true
 }
 
 #[inline]
-fn convert_chupou<'a, const A: usize>(
+fn convert_chupou(
   byte: &mut TypedByte,
-  interpreter: &mut Interpreter<A>,
+  interpreter: &mut Interpreter,
 ) -> bool {
-  *byte = interpreter.stack.top();
-  interpreter.stack.pop();
-  true
+  match interpreter.stack.pop() {
+    Ok(a) => {
+      *byte = a;
+      true
+    }
+    Err(err) => {
+      stack_error!(interpreter, err);
+      false
+    }
+  }
 }
 
 #[inline]
-fn conver_fudeu<'a, const A: usize>(
+fn conver_fudeu(
   byte: &mut TypedByte,
-  interpreter: &mut Interpreter<A>,
+  interpreter: &mut Interpreter,
 ) -> bool {
-  let address = byte.force_u32();
-
-  *byte = interpreter.memory[address as usize % A];
-  true
+  match interpreter.cell(byte.force_u32() as usize) {
+    Some(cell) => {
+      *byte = interpreter.memory[cell];
+      true
+    }
+    None => false,
+  }
 }
 
 #[inline]
-fn convert_penetrou<'a, const A: usize>(
+fn convert_penetrou(
   byte: &mut TypedByte,
-  interpreter: &mut Interpreter<A>,
+  interpreter: &mut Interpreter,
 ) -> bool {
-  let a = interpreter.stack.top();
-  let address = a.force_u32();
-  interpreter.stack.pop();
-  *byte = interpreter.memory[address as usize % A];
-  true
+  let address = match interpreter.stack.pop() {
+    Ok(a) => a.force_u32(),
+    Err(err) => {
+      stack_error!(interpreter, err);
+      return false;
+    }
+  };
+  match interpreter.cell(address as usize) {
+    Some(cell) => {
+      *byte = interpreter.memory[cell];
+      true
+    }
+    None => false,
+  }
 }
 
-fn dns<const A: usize>(int: &mut Interpreter<A>, _: &mut dyn Infra) {
-  int.err =
-    someierror!("Operation is not operating, please suck my dick");
-  return;
+fn dns(int: &mut Interpreter, _: &mut dyn Infra) {
+  int.err = runtime_error!(int, InvalidOpcode);
 }
 // OPCODE 1
 #[inline]
-fn operations<const A: usize>(
-  interpreter: &mut Interpreter<A>,
+fn operations(
+  interpreter: &mut Interpreter,
   _: &mut dyn Infra, // [mut param1, mut param2, mut param3]: [(TypedByte, usize, bool); 3],
 ) {
   let mut param1 = interpreter.instruction.params[0];
@@ -141,110 +600,154 @@ fn operations<const A: usize>(
     param3.0.convert(param2.0.r#type);
   }
 
-  OPERATIONS[kind as usize][param2.0.r#type as usize](
-    &mut param2.0,
-    param3.0.value,
-  );
+  if (kind == 3 || kind == 4)
+    && !matches!(param2.0.r#type, Type::Floating | Type::Floating64)
+    && param3.0.value == [0; 8]
+  {
+    interpreter.err = runtime_error!(interpreter, DivisionByZero);
+    return;
+  }
+
+  let Some(operation) = OPERATIONS
+    .get(kind as usize)
+    .and_then(|a| a[param2.0.r#type as usize])
+  else {
+    interpreter.err = runtime_error!(
+      interpreter,
+      InvalidOperation {
+        kind: kind,
+        r#type: param2.0.r#type,
+      }
+    );
+    return;
+  };
+  if !operation(&mut param2.0, param3.0, interpreter.arithmetic) {
+    interpreter.err = runtime_error!(interpreter, Overflow);
+    return;
+  }
 
-  interpreter.stack.push(param2.0);
+  push!(interpreter, param2.0);
 }
 
-//OPCODE 2
+/// Orders two values of the same type, floats are equal within 4
+/// decimal places and unordered when NaN
+pub fn compare(
+  left: TypedByte,
+  right: TypedByte,
+) -> Result<Option<Ordering>, (Type, Type)> {
+  if left.r#type != right.r#type {
+    return Err((left.r#type, right.r#type));
+  }
+  Ok(match left.r#type {
+    Type::Usigned => {
+      Some(u32_from_bytes(*left).cmp(&u32_from_bytes(*right)))
+    }
+    Type::Signed => {
+      Some(i32_from_bytes(*left).cmp(&i32_from_bytes(*right)))
+    }
+    Type::Floating => {
+      let (left, right) =
+        (f32_from_bytes(*left), f32_from_bytes(*right));
+      if approx_equal(left, right, 4) {
+        Some(Ordering::Equal)
+      } else {
+        left.partial_cmp(&right)
+      }
+    }
+    Type::Usigned64 => Some(
+      u64_from_bytes(left.value).cmp(&u64_from_bytes(right.value)),
+    ),
+    Type::Signed64 => Some(
+      i64_from_bytes(left.value).cmp(&i64_from_bytes(right.value)),
+    ),
+    Type::Floating64 => {
+      let (left, right) =
+        (f64_from_bytes(left.value), f64_from_bytes(right.value));
+      if approx_equal_f64(left, right, 4) {
+        Some(Ordering::Equal)
+      } else {
+        left.partial_cmp(&right)
+      }
+    }
+  })
+}
+
+/// Indexed by the kind of opcode 23: equal, not equal, lower, lower
+/// or equal, greater and greater or equal
+pub const COMPARISONS: [fn(Option<Ordering>) -> bool; 6] = [
+  |a| a == Some(Ordering::Equal),
+  |a| a != Some(Ordering::Equal),
+  |a| a == Some(Ordering::Less),
+  |a| matches!(a, Some(Ordering::Less | Ordering::Equal)),
+  |a| a == Some(Ordering::Greater),
+  |a| matches!(a, Some(Ordering::Greater | Ordering::Equal)),
+];
+
+/// Jumps to param3 when param1 compared to param2 passes
+/// `COMPARISONS[kind]`
 #[inline]
-fn if_lower<const A: usize>(
-  interpreter: &mut Interpreter<A>,
-  _: &mut dyn Infra,
-) {
+fn jump_if(interpreter: &mut Interpreter, kind: usize) {
   let mut param1 = interpreter.instruction.params[0];
   let mut param2 = interpreter.instruction.params[1];
   let mut param3 = interpreter.instruction.params[2];
+
   convert!(interpreter, param1);
   convert!(interpreter, param2);
   convert!(interpreter, param3);
-
   let pos = force_u32!(interpreter, param3.0);
 
-  match param1.0.r#type {
-    Type::Usigned => {
-      if u32_from_bytes(*param1.0) < u32_from_bytes(*param2.0) {
-        interpreter.index = (pos * 15) as usize;
-      }
-    }
-    Type::Signed => {
-      if i32_from_bytes(*param1.0) < i32_from_bytes(*param2.0) {
-        interpreter.index = (pos * 15) as usize;
+  match compare(param1.0, param2.0) {
+    Ok(ordering) => {
+      if COMPARISONS[kind](ordering) {
+        interpreter.index = pos as usize;
       }
     }
-    Type::Floating => {
-      if i32_from_bytes(*param1.0) < i32_from_bytes(*param2.0) {
-        interpreter.index = (pos * 15) as usize;
-      }
+    Err((left, right)) => {
+      interpreter.err = runtime_error!(
+        interpreter,
+        TypeMismatch {
+          left: left,
+          right: right
+        }
+      );
     }
   }
 }
 
+//OPCODE 2
+#[inline]
+fn if_lower(interpreter: &mut Interpreter, _: &mut dyn Infra) {
+  jump_if(interpreter, 2);
+}
+
 //OPCODE 3
 #[inline]
-fn push_stack<const A: usize>(
-  interpreter: &mut Interpreter<A>,
-  _: &mut dyn Infra,
-) {
+fn push_stack(interpreter: &mut Interpreter, _: &mut dyn Infra) {
   let mut param1 = interpreter.instruction.params[0];
   convert!(interpreter, param1);
-  interpreter.stack.push(param1.0);
+  push!(interpreter, param1.0);
 }
 
 //OPCODE 4
 #[inline]
-fn if_true_jump<const A: usize>(
-  interpreter: &mut Interpreter<A>,
-  _: &mut dyn Infra,
-) {
-  let mut param1 = interpreter.instruction.params[0];
-  let mut param2 = interpreter.instruction.params[1];
-  let mut param3 = interpreter.instruction.params[2];
-
-  convert!(interpreter, param1);
-  convert!(interpreter, param2);
-  convert!(interpreter, param3);
-  let pos = force_u32!(interpreter, param3.0);
-
-  if param1.0.r#type != param2.0.r#type {
-    interpreter.err = someierror!("Comparing incompatible types");
-    return;
-  }
-
-  if param1.0.r#type == Type::Floating {
-    let value = f32_from_bytes(*param1.0);
-    let value2 = f32_from_bytes(*param2.0);
-    if approx_equal(value, value2, 4) {
-      interpreter.index = (pos * 15) as usize;
-    }
-  } else if *param1.0 == *param2.0 {
-    interpreter.index = (pos * 15) as usize;
-  };
+fn if_true_jump(interpreter: &mut Interpreter, _: &mut dyn Infra) {
+  jump_if(interpreter, 0);
 }
 
 //OPCODE 5
 #[inline(always)]
-fn vstack_jump<const A: usize>(
-  interpreter: &mut Interpreter<A>,
-  _: &mut dyn Infra,
-) {
+fn vstack_jump(interpreter: &mut Interpreter, _: &mut dyn Infra) {
   let mut param1 = interpreter.instruction.params[0];
 
   convert!(interpreter, param1);
   let value = force_u32!(interpreter, param1.0);
   interpreter.index =
-    ((value * 15) * !interpreter.stack.is_empty() as u32) as usize;
+    value as usize * !interpreter.stack.is_empty() as usize;
 }
 
 //OPCODE 6
 #[inline(always)]
-fn input<const A: usize>(
-  interpreter: &mut Interpreter<A>,
-  infra: &mut dyn Infra,
-) {
+fn input(interpreter: &mut Interpreter, infra: &mut dyn Infra) {
   let mut param1 = interpreter.instruction.params[0];
   let mut param2 = interpreter.instruction.params[1];
   let mut param3 = interpreter.instruction.params[2];
@@ -257,147 +760,115 @@ fn input<const A: usize>(
   let kind = force_u32!(interpreter, param2.0);
   let limit = force_u32!(interpreter, param3.0) as usize;
 
-  try_err!(interpreter, std::io::stdout().flush());
-  let buff = try_err!(interpreter, infra.read_line());
+  try_err!(interpreter, Io, std::io::stdout().flush());
+  let buff = match interpreter.input.take() {
+    Some(a) => a,
+    None => try_err!(interpreter, Io, infra.read_line()),
+  };
 
   match kind {
     1 => {
       match buff.trim().parse::<u32>() {
-        Ok(x) => interpreter.memory[value % A] = x.into(),
-        Err(_) => interpreter.stack.push(1u32.into()),
+        Ok(x) => *cell!(interpreter, value) = x.into(),
+        Err(_) => push!(interpreter, 1u32.into()),
       };
     }
     2 => match buff.trim().parse::<i32>() {
-      Ok(x) => interpreter.memory[value % A] = x.into(),
-      Err(_) => interpreter.stack.push(1u32.into()),
+      Ok(x) => *cell!(interpreter, value) = x.into(),
+      Err(_) => push!(interpreter, 1u32.into()),
     },
     3 => match buff.trim().parse::<f32>() {
-      Ok(x) => interpreter.memory[value % A] = x.into(),
-      Err(_) => interpreter.stack.push(1u32.into()),
+      Ok(x) => *cell!(interpreter, value) = x.into(),
+      Err(_) => push!(interpreter, 1u32.into()),
     },
     _ => {
       for (i, char) in buff.chars().enumerate() {
         if i < limit {
           let char = if char == '\n' || char == '\0' {
-            interpreter.memory[value % A] = [0; 4].into();
+            *cell!(interpreter, value) = [0; 4].into();
             continue;
           } else {
             char
           };
-          let mut bytes: [u8; 4] = [0, 0, 0, 0];
-
-          char.encode_utf8(&mut bytes);
-
-          interpreter.memory[value % A] =
-            (u32_from_bytes(bytes)).into();
+          *cell!(interpreter, value) = char_to_u32(char).into();
           value += 1;
         } else {
           break;
         }
       }
-      interpreter.memory[value % A] = 0u32.into();
+      *cell!(interpreter, value) = 0u32.into();
     }
   };
 }
 
 //OPCODE 7
 #[inline(always)]
-fn print<const A: usize>(
-  interpreter: &mut Interpreter<A>,
-  infra: &mut dyn Infra,
-) {
-  if interpreter.stack.sx == 0 {
-    interpreter.err = someierror!(
-      "Trying to use the stack while empty in command {}",
-      interpreter.current_command()
-    );
-    return;
-  }
-  let stack_byte = top!(interpreter, interpreter.stack);
+fn print(interpreter: &mut Interpreter, infra: &mut dyn Infra) {
+  let stack_byte = pop!(interpreter);
 
   infra.print(&*stack_byte);
-
-  interpreter.stack.pop();
 }
 
 //OPCODE 8
 #[inline(always)]
-fn printnumber<const A: usize>(
-  interpreter: &mut Interpreter<A>,
-  infra: &mut dyn Infra,
-) {
-  let TypedByte { value, r#type } =
-    top!(interpreter, interpreter.stack);
-
-  match r#type {
-    Type::Floating => {
-      infra.print(f32_from_bytes(value).to_string().as_bytes())
-    }
-    Type::Signed => {
-      infra.print(i32_from_bytes(value).to_string().as_bytes())
-    }
-    Type::Usigned => {
-      infra.print(u32_from_bytes(value).to_string().as_bytes())
-    }
-  }
-
-  interpreter.stack.pop();
+fn printnumber(interpreter: &mut Interpreter, infra: &mut dyn Infra) {
+  let byte = pop!(interpreter);
+
+  let number = match byte.r#type {
+    Type::Floating => f32_from_bytes(*byte).to_string(),
+    Type::Signed => i32_from_bytes(*byte).to_string(),
+    Type::Usigned => u32_from_bytes(*byte).to_string(),
+    Type::Floating64 => f64_from_bytes(byte.value).to_string(),
+    Type::Signed64 => i64_from_bytes(byte.value).to_string(),
+    Type::Usigned64 => u64_from_bytes(byte.value).to_string(),
+  };
+  infra.print(number.as_bytes());
 }
 
 //OPCODE 9
 #[inline(always)]
-fn jump<const A: usize>(
-  interpreter: &mut Interpreter<A>,
-  _: &mut dyn Infra,
-) {
+fn jump(interpreter: &mut Interpreter, _: &mut dyn Infra) {
   let mut param1 = interpreter.instruction.params[0];
 
   convert!(interpreter, param1);
   let value = force_u32!(interpreter, param1.0);
-  interpreter.index = (value * 15) as usize;
+  interpreter.index = value as usize;
 }
 
 //OPCODE 10
 #[inline(always)]
-fn set<const A: usize>(
-  interpreter: &mut Interpreter<A>,
-  _: &mut dyn Infra,
-) {
+fn set(interpreter: &mut Interpreter, _: &mut dyn Infra) {
   let mut param1 = interpreter.instruction.params[0];
 
   convert!(interpreter, param1);
 
   let address = force_u32!(interpreter, param1.0) as usize;
-  let typed_byte = top!(interpreter, interpreter.stack);
-  interpreter.stack.pop();
-  interpreter.memory[address % A] = typed_byte;
+  let typed_byte = pop!(interpreter);
+  *cell!(interpreter, address) = typed_byte;
 }
 
 //OPCODE 11
 #[inline(always)]
-fn pop_stack<const A: usize>(
-  interpreter: &mut Interpreter<A>,
-  _: &mut dyn Infra,
-) {
-  interpreter.stack.pop();
+fn pop_stack(interpreter: &mut Interpreter, _: &mut dyn Infra) {
+  pop!(interpreter);
 }
 
 //OPCODE 12
 #[inline(always)]
-fn load_string<const A: usize>(
-  interpreter: &mut Interpreter<A>,
-  _: &mut dyn Infra,
-) {
+fn load_string(interpreter: &mut Interpreter, _: &mut dyn Infra) {
   let mut param1 = interpreter.instruction.params[0];
 
   convert!(interpreter, param1);
-  let mut value = force_u32!(interpreter, param1.0) as usize;
+  let address = force_u32!(interpreter, param1.0) as usize;
+  let Some(mut value) = interpreter.cell(address) else {
+    return;
+  };
   let mut buffer: Vec<u32> = Vec::new();
   loop {
     if value == interpreter.memory.len() {
       break;
     }
-    let temp = u32_from_bytes(*interpreter.memory[value % A]);
+    let temp = u32_from_bytes(*interpreter.memory[value]);
     if temp != 0 {
       buffer.push(temp);
       value += 1;
@@ -407,19 +878,23 @@ fn load_string<const A: usize>(
   }
   buffer.reverse();
   for i in buffer {
-    interpreter.stack.push(TypedByte {
-      value: u32_to_bytes(i),
-      r#type: Type::Usigned,
-    });
+    push!(interpreter, i.into());
   }
 }
 
+/// A 64-bit value pushed as two 32-bit halves, before the 64-bit
+/// types existed
+#[inline]
+fn join_halves(low: [u8; 4], high: [u8; 4]) -> u64 {
+  u64_from_bytes([
+    low[0], low[1], low[2], low[3], high[0], high[1], high[2],
+    high[3],
+  ])
+}
+
 //OPCODE 13
 #[inline(always)]
-fn time_operations<const A: usize>(
-  interpreter: &mut Interpreter<A>,
-  _: &mut dyn Infra,
-) {
+fn time_operations(interpreter: &mut Interpreter, _: &mut dyn Infra) {
   let mut param1 = interpreter.instruction.params[0];
 
   convert!(interpreter, param1);
@@ -430,14 +905,15 @@ fn time_operations<const A: usize>(
     }
     //SET bx
     1 => {
-      let a = top!(interpreter, interpreter.stack).value;
-      interpreter.stack.pop();
-      let b = top!(interpreter, interpreter.stack).value;
-      let result = [b[0], b[1], b[2], b[3], a[0], a[1], a[2], a[3]];
+      let mut a = pop!(interpreter);
+      let millis = if a.is_wide() {
+        a.convert(Type::Usigned64);
+        u64_from_bytes(a.value)
+      } else {
+        join_halves(*top!(interpreter), *a)
+      };
 
-      interpreter.duration = Some(Duration::from_millis(unsafe {
-        std::mem::transmute(result)
-      }));
+      interpreter.duration = Some(Duration::from_millis(millis));
     }
     // CMP ax elapsed to bx
     2 => {
@@ -446,13 +922,13 @@ fn time_operations<const A: usize>(
           let elapsed = a.elapsed();
           match elapsed.cmp(&b) {
             std::cmp::Ordering::Less => {
-              interpreter.stack.push(0u32.into())
+              push!(interpreter, 0u32.into())
             }
             std::cmp::Ordering::Equal => {
-              interpreter.stack.push(1u32.into())
+              push!(interpreter, 1u32.into())
             }
             std::cmp::Ordering::Greater => {
-              interpreter.stack.push(2u32.into())
+              push!(interpreter, 2u32.into())
             }
           }
         }
@@ -463,97 +939,83 @@ fn time_operations<const A: usize>(
 }
 //OPCODE 14
 #[inline(always)]
-fn flush<const A: usize>(
-  _: &mut Interpreter<A>,
-  infra: &mut dyn Infra,
-) {
+fn flush(_: &mut Interpreter, infra: &mut dyn Infra) {
   infra.flush();
 }
 //OPCODE 15
 #[inline(always)]
-fn terminal_commands<const A: usize>(
-  interpreter: &mut Interpreter<A>,
+fn terminal_commands(
+  interpreter: &mut Interpreter,
   infra: &mut dyn Infra,
 ) {
   let mut param1 = interpreter.instruction.params[0];
 
   convert!(interpreter, param1);
 
-  match force_u32!(interpreter, param1.0) {
+  let kind = force_u32!(interpreter, param1.0);
+  match kind {
     // RAW MODE
     0 => {
-      let on_off =
-        force_u32!(interpreter, top!(interpreter, interpreter.stack));
-      interpreter.stack.pop();
+      let on_off = force_u32!(interpreter, pop!(interpreter));
       if on_off == 0 {
-        try_err!(interpreter, infra.disable_raw_mode());
+        try_err!(interpreter, Terminal, infra.disable_raw_mode());
       } else {
-        try_err!(interpreter, infra.enable_raw_mode());
+        try_err!(interpreter, Terminal, infra.enable_raw_mode());
       }
     }
     // CLEAR
     1 => {
-      let r#type =
-        force_u32!(interpreter, top!(interpreter, interpreter.stack));
-      interpreter.stack.pop();
+      let r#type = force_u32!(interpreter, pop!(interpreter));
       if r#type == 0 {
-        try_err!(interpreter, infra.clear_purge());
+        try_err!(interpreter, Terminal, infra.clear_purge());
       } else {
-        try_err!(interpreter, infra.clear_all());
+        try_err!(interpreter, Terminal, infra.clear_all());
       }
     }
     // POLL KEYBOARD
     2 => {
-      let a = *top!(interpreter, interpreter.stack);
-      interpreter.stack.pop();
-      let b = *top!(interpreter, interpreter.stack);
-      interpreter.stack.pop();
-      let result = [b[0], b[1], b[2], b[3], a[0], a[1], a[2], a[3]];
-      let value = try_err!(
-        interpreter,
-        infra.poll(unsafe {
-          std::mem::transmute::<[u8; 8], u64>(result)
-        })
-      );
-      interpreter.stack.push(value.into());
+      let mut a = pop!(interpreter);
+      let millis = if a.is_wide() {
+        a.convert(Type::Usigned64);
+        u64_from_bytes(a.value)
+      } else {
+        join_halves(*pop!(interpreter), *a)
+      };
+      let value = try_err!(interpreter, Terminal, infra.poll(millis));
+      push!(interpreter, value.into());
     }
     // SHOW/HIDE CURSOR
     3 => {
-      let on_off =
-        force_u32!(interpreter, top!(interpreter, interpreter.stack));
-      interpreter.stack.pop();
+      let on_off = force_u32!(interpreter, pop!(interpreter));
       if on_off == 0 {
-        try_err!(interpreter, infra.hide_cursor());
+        try_err!(interpreter, Terminal, infra.hide_cursor());
       } else {
-        try_err!(interpreter, infra.show_cursor());
+        try_err!(interpreter, Terminal, infra.show_cursor());
       }
     }
     // MOVE CURSOR
     4 => {
-      let x =
-        force_u32!(interpreter, top!(interpreter, interpreter.stack));
-      interpreter.stack.pop();
-      let y =
-        force_u32!(interpreter, top!(interpreter, interpreter.stack));
-      interpreter.stack.pop();
-      try_err!(interpreter, infra.move_cursor(x, y));
+      let x = force_u32!(interpreter, pop!(interpreter));
+      let y = force_u32!(interpreter, pop!(interpreter));
+      try_err!(interpreter, Terminal, infra.move_cursor(x, y));
     }
     // FONT COLOR
     5 => {
-      let color =
-        force_u32!(interpreter, top!(interpreter, interpreter.stack));
-      interpreter.stack.pop();
-      try_err!(interpreter, infra.use_color(color));
+      let color = force_u32!(interpreter, pop!(interpreter));
+      try_err!(interpreter, Terminal, infra.use_color(color));
     }
     //BACKGROUND
     6 => {
-      let color =
-        force_u32!(interpreter, top!(interpreter, interpreter.stack));
-      interpreter.stack.pop();
-      try_err!(interpreter, infra.use_background(color));
+      let color = force_u32!(interpreter, pop!(interpreter));
+      try_err!(interpreter, Terminal, infra.use_background(color));
     }
     _ => {
-      interpreter.err = someierror!("Invalid terminal command");
+      interpreter.err = runtime_error!(
+        interpreter,
+        Terminal {
+          message: format!("Invalid terminal command {}", kind)
+        }
+      );
       return;
     }
   }
@@ -561,22 +1023,128 @@ fn terminal_commands<const A: usize>(
 
 // OPCODE 16
 #[inline(always)]
-fn random<const A: usize>(
-  interpreter: &mut Interpreter<A>,
+fn random(interpreter: &mut Interpreter, _: &mut dyn Infra) {
+  let mut rng = rand::thread_rng();
+  push!(interpreter, rng.gen::<f32>().into());
+}
+
+// OPCODE 17
+#[inline(always)]
+fn call(interpreter: &mut Interpreter, _: &mut dyn Infra) {
+  let mut param1 = interpreter.instruction.params[0];
+
+  convert!(interpreter, param1);
+  let value = force_u32!(interpreter, param1.0);
+  if interpreter.calls.len() >= interpreter.call_depth {
+    interpreter.err = runtime_error!(interpreter, CallStackOverflow);
+    return;
+  }
+  interpreter.calls.push(interpreter.index);
+  interpreter.index = value as usize;
+}
+
+// OPCODE 18
+#[inline(always)]
+fn ret(interpreter: &mut Interpreter, _: &mut dyn Infra) {
+  match interpreter.calls.pop() {
+    Some(index) => interpreter.index = index,
+    None => {
+      interpreter.err =
+        runtime_error!(interpreter, CallStackUnderflow)
+    }
+  }
+}
+
+// OPCODE 19
+#[inline(always)]
+fn if_greater(interpreter: &mut Interpreter, _: &mut dyn Infra) {
+  jump_if(interpreter, 4);
+}
+
+// OPCODE 20
+#[inline(always)]
+fn if_lower_equal(interpreter: &mut Interpreter, _: &mut dyn Infra) {
+  jump_if(interpreter, 3);
+}
+
+// OPCODE 21
+#[inline(always)]
+fn if_greater_equal(
+  interpreter: &mut Interpreter,
   _: &mut dyn Infra,
 ) {
-  let mut rng = rand::thread_rng();
-  interpreter.stack.push(rng.gen::<f32>().into());
+  jump_if(interpreter, 5);
+}
+
+// OPCODE 22
+#[inline(always)]
+fn if_not_equal(interpreter: &mut Interpreter, _: &mut dyn Infra) {
+  jump_if(interpreter, 1);
+}
+
+// OPCODE 23
+#[inline(always)]
+fn comparison(interpreter: &mut Interpreter, _: &mut dyn Infra) {
+  let mut param1 = interpreter.instruction.params[0];
+  let mut param2 = interpreter.instruction.params[1];
+  let mut param3 = interpreter.instruction.params[2];
+
+  convert!(interpreter, param1);
+  convert!(interpreter, param2);
+  convert!(interpreter, param3);
+  let kind = force_u32!(interpreter, param1.0);
+
+  let Some(condition) = COMPARISONS.get(kind as usize) else {
+    interpreter.err = runtime_error!(
+      interpreter,
+      InvalidOperation {
+        kind: kind,
+        r#type: param2.0.r#type,
+      }
+    );
+    return;
+  };
+  match compare(param2.0, param3.0) {
+    Ok(ordering) => {
+      push!(interpreter, (condition(ordering) as u32).into());
+    }
+    Err((left, right)) => {
+      interpreter.err = runtime_error!(
+        interpreter,
+        TypeMismatch {
+          left: left,
+          right: right
+        }
+      );
+    }
+  }
 }
 
-impl<'a, const A: usize> Interpreter<'a, A> {
-  pub const fn new(buffer: &'a [u8]) -> Self {
+impl<'a> Interpreter<'a> {
+  /// Uses the `InterpreterBuilder` defaults
+  pub fn new(buffer: &'a [u8]) -> Self {
+    Self::builder().build(buffer)
+  }
+
+  pub fn builder() -> InterpreterBuilder {
+    InterpreterBuilder::default()
+  }
+
+  fn with_sizes(
+    buffer: &'a [u8],
+    builder: &InterpreterBuilder,
+  ) -> Self {
+    let (instructions, invalid) = match Self::decode(buffer) {
+      Ok(instructions) => (instructions, None),
+      Err(problem) => (Vec::new(), Some(problem)),
+    };
     Self {
-      memory: [TypedByte {
-        r#type: Type::Usigned,
-        value: [0; 4],
-      }; A],
-      stack: Stack::new(),
+      memory: vec![TypedByte::default(); builder.memory_cells],
+      stack: Stack::new(builder.stack_depth),
+      calls: Vec::new(),
+      call_depth: builder.call_depth,
+      max_memory: builder.memory_limit(),
+      address_policy: builder.address_policy,
       debug: false,
       time: None,
       duration: None,
@@ -604,13 +1172,13 @@ impl<'a, const A: usize> Interpreter<'a, A> {
         flush,
         terminal_commands,
         random,
-        dns,
-        dns,
-        dns,
-        dns,
-        dns,
-        dns,
-        dns,
+        call,
+        ret,
+        if_greater,
+        if_lower_equal,
+        if_greater_equal,
+        if_not_equal,
+        comparison,
         dns,
         dns,
         dns,
@@ -847,114 +1415,523 @@ impl<'a, const A: usize> Interpreter<'a, A> {
       instruction: Instruction::new(),
       index: 0,
       err: None,
+      command: 0,
+      arithmetic: Arithmetic::Trap,
       buffer,
+      instructions,
+      invalid,
+      require_verified: false,
+      verified: false,
+      breakpoints: Vec::new(),
+      stopped_at: None,
+      symbols: Vec::new(),
+      source_map: None,
+      fuel: None,
+      fuel_costs: [1; 256],
+      deadline: None,
+      executed: 0,
+      suspend_on_input: false,
+      input: None,
+      waiting_input: false,
+    }
+  }
+
+  /// Validates a container written by `Compiler::write_container`
+  /// and runs its code section
+  pub fn load(container: &'a [u8]) -> Result<Self, IError> {
+    Self::builder().load(container)
+  }
+
+  /// Maps an address to a memory cell, growing the memory up to its
+  /// limit before applying the address policy
+  fn cell(&mut self, address: usize) -> Option<usize> {
+    if address < self.memory.len() {
+      return Some(address);
+    }
+    if address < self.max_memory {
+      self.memory.resize(address + 1, TypedByte::default());
+      return Some(address);
+    }
+    match self.address_policy {
+      AddressPolicy::Wrap if !self.memory.is_empty() => {
+        Some(address % self.memory.len())
+      }
+      _ => {
+        self.err =
+          runtime_error!(self, MemoryOutOfRange { address: address });
+        None
+      }
     }
   }
 
   pub fn current_command(&self) -> usize {
-    self.index / 15
+    self.index
   }
 
-  pub fn get_bytes_data(buffer: [u8; 15], inst: &mut Instruction) {
-    let converted_types = convert_kind_byte(buffer[14]);
-    let converted_kind = convert_kind_byte(buffer[1]);
+  /// Fails with the verifier's problem on a type nibble past `TYPES`
+  /// or an unknown flag, so a decoded record encodes back the same
+  pub fn get_bytes_data(
+    record: Record,
+    inst: &mut Instruction,
+  ) -> Result<(), ProblemKind> {
+    let converted_types = convert_types_bytes(record.types_bytes);
+    let converted_kind = convert_kind_byte(record.kind_byte);
 
     let [param1_convert, param2_convert] =
       convert_two_bits(converted_kind[3] as u8);
-    let [param3_convert, _] =
-      convert_two_bits(converted_types[3] as u8);
-
-    inst.opcode = buffer[0];
+    let converts =
+      [param1_convert, param2_convert, converted_types[3] & 1 != 0];
 
-    inst.params[0].0 = TypedByte {
-      value: [buffer[2], buffer[3], buffer[4], buffer[5]],
-      r#type: Type::from(converted_types[0]),
-    };
-    inst.params[0].2 = param1_convert;
-    inst.params[0].1 = converted_kind[0];
-
-    inst.params[1].0 = TypedByte {
-      value: [buffer[6], buffer[7], buffer[8], buffer[9]],
-      r#type: Type::from(converted_types[1]),
-    };
-    inst.params[1].2 = param2_convert;
-    inst.params[1].1 = converted_kind[1];
+    inst.opcode = record.opcode;
 
-    inst.params[2].0 = TypedByte {
-      value: [buffer[10], buffer[11], buffer[12], buffer[13]],
-      r#type: Type::from(converted_types[2]),
-    };
-    inst.params[2].2 = param3_convert;
-    inst.params[2].1 = converted_kind[2];
+    for (i, param) in inst.params.iter_mut().enumerate() {
+      let r#type =
+        Type::try_from(converted_types[i] as u8).map_err(|bits| {
+          ProblemKind::InvalidType {
+            param: i + 1,
+            bits: bits as usize,
+          }
+        })?;
+      *param = (
+        TypedByte {
+          value: record.params[i],
+          r#type,
+        },
+        converted_kind[i],
+        converts[i],
+      );
+    }
+    if converted_types[3] > 1 {
+      return Err(ProblemKind::InvalidFlags);
+    }
+    Ok(())
+  }
+  /// Decodes every record of `buffer`, so steps index them by command
+  /// instead of parsing bytes
+  pub fn decode(buffer: &[u8]) -> Result<Vec<Instruction>, Problem> {
+    Records::new(buffer)
+      .enumerate()
+      .map(|(command, record)| {
+        let record = record.map_err(|offset| Problem {
+          command,
+          kind: ProblemKind::Truncated { offset },
+        })?;
+        let mut instruction = Instruction::new();
+        Self::get_bytes_data(record, &mut instruction)
+          .map_err(|kind| Problem { command, kind })?;
+        Ok(instruction)
+      })
+      .collect()
   }
   pub fn debug(&mut self, new: bool) {
     self.debug = new;
   }
+  pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) {
+    self.arithmetic = arithmetic;
+  }
+  /// `None` runs without limit
+  pub fn set_fuel(&mut self, fuel: Option<u64>) {
+    self.fuel = fuel;
+  }
+  /// Refuels after an `OutOfFuel`, does nothing without a limit
+  pub fn add_fuel(&mut self, fuel: u64) {
+    if let Some(a) = &mut self.fuel {
+      *a = a.saturating_add(fuel);
+    }
+  }
+  pub fn fuel(&self) -> Option<u64> {
+    self.fuel
+  }
+  /// Every opcode costs 1 by default
+  pub fn set_fuel_cost(&mut self, opcode: u8, cost: u64) {
+    self.fuel_costs[opcode as usize] = cost;
+  }
+  /// Checked every `DEADLINE_INTERVAL` instructions
+  pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+    self.deadline = deadline;
+  }
+  /// How many instructions ran so far
+  pub fn executed(&self) -> u64 {
+    self.executed
+  }
+  /// Makes the input opcode wait for `provide_input` instead of
+  /// reading from `Infra::read_line`
+  pub fn suspend_on_input(&mut self, new: bool) {
+    self.suspend_on_input = new;
+  }
+  /// The line the next input opcode reads
+  pub fn provide_input(&mut self, line: String) {
+    self.input = Some(line);
+    self.waiting_input = false;
+  }
+  /// Refuses to run a buffer that doesn't pass `verifier::verify`
+  pub fn require_verified(&mut self, new: bool) {
+    self.require_verified = new;
+  }
 
+  /// Runs until the program ends, failing with `WaitingForInput`
+  /// when suspended on input
   pub fn run_buffer(
     &mut self,
     infra: &mut dyn Infra,
-  ) -> Result<(), IError> {
+  ) -> Result<(), RuntimeError> {
+    while self.step(infra)? {}
+    self.check_waiting()
+  }
+
+  fn check_waiting(&self) -> Result<(), RuntimeError> {
+    if self.waiting_input {
+      return Err(RuntimeError::WaitingForInput {
+        context: self.error_context(),
+      });
+    }
+    Ok(())
+  }
+
+  /// Runs at most `instructions` instructions
+  pub fn run_for(
+    &mut self,
+    infra: &mut dyn Infra,
+    instructions: usize,
+  ) -> ExecutionState {
+    for _ in 0..instructions {
+      match self.step(infra) {
+        Ok(true) => {}
+        Ok(false) => return self.stopped_state(),
+        Err(err) => return ExecutionState::Errored(err),
+      }
+    }
+    ExecutionState::Running
+  }
+
+  /// Runs until the program ends, fails or waits for input
+  pub fn resume(&mut self, infra: &mut dyn Infra) -> ExecutionState {
     loop {
-      if self.index + 15 > self.buffer.len() {
-        break;
+      match self.step(infra) {
+        Ok(true) => {}
+        Ok(false) => return self.stopped_state(),
+        Err(err) => return ExecutionState::Errored(err),
       }
-      let converted_types =
-        convert_kind_byte(self.buffer[self.index + 14]);
-      let converted_kind =
-        convert_kind_byte(self.buffer[self.index + 1]);
-
-      let [param1_convert, param2_convert] =
-        convert_two_bits(converted_kind[3] as u8);
-      let [param3_convert, _] =
-        convert_two_bits(converted_types[3] as u8);
-
-      self.instruction.opcode = self.buffer[self.index];
-
-      self.instruction.params[0].0 = TypedByte {
-        value: [
-          self.buffer[self.index + 2],
-          self.buffer[self.index + 3],
-          self.buffer[self.index + 4],
-          self.buffer[self.index + 5],
-        ],
-        r#type: Type::from(converted_types[0]),
-      };
-      self.instruction.params[0].2 = param1_convert;
-      self.instruction.params[0].1 = converted_kind[0];
-
-      self.instruction.params[1].0 = TypedByte {
-        value: [
-          self.buffer[self.index + 6],
-          self.buffer[self.index + 7],
-          self.buffer[self.index + 8],
-          self.buffer[self.index + 9],
-        ],
-        r#type: Type::from(converted_types[1]),
-      };
-      self.instruction.params[1].2 = param2_convert;
-      self.instruction.params[1].1 = converted_kind[1];
-
-      self.instruction.params[2].0 = TypedByte {
-        value: [
-          self.buffer[self.index + 10],
-          self.buffer[self.index + 11],
-          self.buffer[self.index + 12],
-          self.buffer[self.index + 13],
-        ],
-        r#type: Type::from(converted_types[2]),
-      };
-      self.instruction.params[2].2 = param3_convert;
-      self.instruction.params[2].1 = converted_kind[2];
+    }
+  }
+
+  fn stopped_state(&self) -> ExecutionState {
+    if self.waiting_input {
+      ExecutionState::WaitingForInput
+    } else {
+      ExecutionState::Halted
+    }
+  }
+
+  /// Executes the next instruction, returning false once the program
+  /// has ended or is waiting for input
+  pub fn step(
+    &mut self,
+    infra: &mut dyn Infra,
+  ) -> Result<bool, RuntimeError> {
+    if self.require_verified && !self.verified {
+      if let Err(problems) = verify(self.buffer) {
+        return Err(RuntimeError::Unverified {
+          context: self.error_context(),
+          problems,
+        });
+      }
+      self.verified = true;
+    }
+    if let Some(problem) = self.invalid {
+      return Err(RuntimeError::Unverified {
+        context: self.error_context(),
+        problems: vec![problem],
+      });
+    }
+    match self.instructions.get(self.current_command()) {
+      Some(instruction) => self.instruction = *instruction,
+      None => return Ok(false),
+    }
+    self.command = self.current_command();
+
+    if self.instruction.opcode == 6
+      && self.suspend_on_input
+      && self.input.is_none()
+    {
+      self.waiting_input = true;
+      return Ok(false);
+    }
+
+    if let Some(deadline) = self.deadline {
+      if self.executed.is_multiple_of(DEADLINE_INTERVAL)
+        && Instant::now() >= deadline
+      {
+        return Err(RuntimeError::DeadlineExceeded {
+          context: self.error_context(),
+        });
+      }
+    }
+    if let Some(fuel) = self.fuel {
+      let cost = self.fuel_costs[self.instruction.opcode as usize];
+      if fuel < cost {
+        return Err(RuntimeError::OutOfFuel {
+          context: self.error_context(),
+        });
+      }
+      self.fuel = Some(fuel - cost);
+    }
 
-      self.index += 15;
-      self.execute_command(infra);
+    if self.debug {
+      infra.println(format!(
+        "command {}\n{}",
+        self.current_command(),
+        self.instruction
+      ));
+    }
+
+    self.index += 1;
+    self.executed += 1;
+    self.execute_command(infra);
+
+    match self.err.take() {
+      Some(err) => Err(err),
+      None => Ok(true),
+    }
+  }
 
-      if let Some(err) = &self.err {
-        return Err(err.clone());
+  /// Runs until the next instruction is a breakpoint, returning its
+  /// index, or `None` if the program ended first. The breakpoint it
+  /// stopped on runs when continuing, waiting for input fails like
+  /// `run_buffer`
+  pub fn continue_until_break(
+    &mut self,
+    infra: &mut dyn Infra,
+  ) -> Result<Option<usize>, RuntimeError> {
+    loop {
+      let command = self.current_command();
+      if self.breakpoints.contains(&command)
+        && self.stopped_at != Some(self.executed)
+      {
+        self.stopped_at = Some(self.executed);
+        return Ok(Some(command));
+      }
+      if !self.step(infra)? {
+        return self.check_waiting().map(|_| None);
       }
     }
-    Ok(())
+  }
+
+  /// Saves the running state, see `restore`
+  ///
+  /// Layout, all integers little endian:
+  /// magic, version u16, crc32 of the buffer, index u32, command u32,
+  /// executed u64, the ax timer elapsed and the bx duration as
+  /// (present u8, nanoseconds u64), memory cell count u32 and cells,
+  /// stack length u32 and values, each value a type u8 and 8 bytes,
+  /// call stack length u32 and return indexes u32, then a crc32 of
+  /// every byte before it
+  pub fn snapshot(&self) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend(SNAPSHOT_MAGIC);
+    out.extend(SNAPSHOT_VERSION.to_le_bytes());
+    out.extend(crc32(self.buffer).to_le_bytes());
+    out.extend((self.index as u32).to_le_bytes());
+    out.extend((self.command as u32).to_le_bytes());
+    out.extend(self.executed.to_le_bytes());
+    for timer in [self.time.map(|a| a.elapsed()), self.duration] {
+      out.push(timer.is_some() as u8);
+      let nanos = timer.map_or(0, |a| a.as_nanos() as u64);
+      out.extend(nanos.to_le_bytes());
+    }
+    let values = [&self.memory[..], self.stack_values()];
+    for values in values {
+      out.extend((values.len() as u32).to_le_bytes());
+      for value in values {
+        out.push(value.r#type as u8);
+        out.extend(value.value);
+      }
+    }
+    out.extend((self.calls.len() as u32).to_le_bytes());
+    for index in &self.calls {
+      out.extend((*index as u32).to_le_bytes());
+    }
+    out.extend(crc32(&out).to_le_bytes());
+    out
+  }
+
+  /// Rebuilds an interpreter saved by `snapshot`, `buffer` must be the
+  /// program it was running
+  pub fn restore(
+    buffer: &'a [u8],
+    snapshot: &[u8],
+  ) -> Result<Self, IError> {
+    Self::builder().restore(buffer, snapshot)
+  }
+
+  fn restore_with(
+    buffer: &'a [u8],
+    snapshot: &[u8],
+    builder: &InterpreterBuilder,
+  ) -> Result<Self, IError> {
+    if snapshot.len() < 10 || snapshot[0..4] != SNAPSHOT_MAGIC {
+      return ierror!("Not a robson snapshot, missing magic");
+    }
+    let version = u16::from_le_bytes([snapshot[4], snapshot[5]]);
+    if version != SNAPSHOT_VERSION {
+      return ierror!(
+        "Unsupported snapshot version {}, expected {}",
+        version,
+        SNAPSHOT_VERSION
+      );
+    }
+    let (body, checksum) = snapshot.split_at(snapshot.len() - 4);
+    if crc32(body).to_le_bytes() != checksum {
+      return ierror!("Corrupted snapshot, checksum mismatch");
+    }
+    let mut reader = Reader::new(&body[6..]);
+    if reader.u32()? != crc32(buffer) {
+      return ierror!("The snapshot was taken from another program");
+    }
+
+    let mut interpreter = Self::with_sizes(buffer, builder);
+    if let Some(problem) = interpreter.invalid {
+      return Err(IError::message(problem));
+    }
+    interpreter.index = reader.u32()? as usize;
+    interpreter.command = reader.u32()? as usize;
+    interpreter.executed = reader.u64()?;
+    if interpreter.index > interpreter.instructions.len() {
+      return ierror!(
+        "Corrupted snapshot, invalid index {}",
+        interpreter.index
+      );
+    }
+    let mut timers = [None; 2];
+    for timer in &mut timers {
+      let present = reader.u8()? != 0;
+      let duration = Duration::from_nanos(reader.u64()?);
+      *timer = present.then_some(duration);
+    }
+    interpreter.time =
+      timers[0].and_then(|a| Instant::now().checked_sub(a));
+    interpreter.duration = timers[1];
+
+    let cells = reader.u32()? as usize;
+    if cells > interpreter.max_memory {
+      return ierror!(
+        "The snapshot has {} memory cells, but only {} are available",
+        cells,
+        interpreter.max_memory
+      );
+    }
+    interpreter.memory = (0..cells)
+      .map(|_| Self::read_value(&mut reader))
+      .collect::<Result<_, _>>()?;
+    for _ in 0..reader.u32()? {
+      let value = Self::read_value(&mut reader)?;
+      if interpreter.stack.push(value).is_err() {
+        return ierror!("Corrupted snapshot, stack too deep");
+      }
+    }
+    for _ in 0..reader.u32()? {
+      let index = reader.u32()? as usize;
+      if interpreter.calls.len() >= interpreter.call_depth
+        || index > interpreter.instructions.len()
+      {
+        return ierror!("Corrupted snapshot, invalid call stack");
+      }
+      interpreter.calls.push(index);
+    }
+    Ok(interpreter)
+  }
+
+  fn read_value(reader: &mut Reader) -> Result<TypedByte, IError> {
+    let Ok(r#type) = Type::try_from(reader.u8()?) else {
+      return ierror!("Corrupted snapshot, invalid type");
+    };
+    let mut value = [0; PARAM_SIZE];
+    value.copy_from_slice(reader.take(PARAM_SIZE)?);
+    Ok(TypedByte { value, r#type })
+  }
+
+  /// Snapshot of the executing command for runtime errors
+  pub fn error_context(&self) -> ErrorContext {
+    let stack = self.stack_values();
+    ErrorContext {
+      command: self.command,
+      opcode: self.instruction.opcode,
+      stack: stack[stack.len().saturating_sub(STACK_VIEW)..].to_vec(),
+      location: self.source_location(self.command).cloned(),
+    }
+  }
+
+  pub fn add_breakpoint(&mut self, command: usize) {
+    if !self.breakpoints.contains(&command) {
+      self.breakpoints.push(command);
+    }
+  }
+  pub fn remove_breakpoint(&mut self, command: usize) {
+    self.breakpoints.retain(|a| *a != command);
+  }
+  pub fn breakpoints(&self) -> &[usize] {
+    &self.breakpoints
+  }
+  /// Needs the labels from `set_symbols` or a loaded container
+  pub fn add_label_breakpoint(
+    &mut self,
+    label: &str,
+  ) -> Result<usize, IError> {
+    let command = self
+      .symbols
+      .iter()
+      .find(|a| a.0 == label)
+      .map(|a| a.1 as usize)
+      .ok_or_else(|| {
+        IError::message(format!("Cant find the label '{label}'"))
+      })?;
+    self.add_breakpoint(command);
+    Ok(command)
+  }
+  pub fn set_symbols(&mut self, symbols: Vec<(String, u32)>) {
+    self.symbols = symbols;
+  }
+  pub fn set_source_map(&mut self, source_map: SourceMap) {
+    self.source_map = Some(source_map);
+  }
+  pub fn source_location(
+    &self,
+    command: usize,
+  ) -> Option<&SourceLocation> {
+    self.source_map.as_ref()?.get(command)
+  }
+  /// Breaks at the first instruction of `path:line`, or of the next
+  /// line holding one. Needs a source map
+  pub fn add_line_breakpoint(
+    &mut self,
+    path: &str,
+    line: usize,
+  ) -> Result<usize, IError> {
+    let command = self
+      .source_map
+      .as_ref()
+      .and_then(|a| a.find_line(path, line))
+      .ok_or_else(|| {
+        IError::message(format!("No instruction at {path}:{line}"))
+      })?;
+    self.add_breakpoint(command);
+    Ok(command)
+  }
+
+  /// The values pushed on the stack, bottom first
+  pub fn stack_values(&self) -> &[TypedByte] {
+    &self.stack.vec[1..=self.stack.sx]
+  }
+  pub fn memory(&self) -> &[TypedByte] {
+    &self.memory
+  }
+  /// The last executed instruction
+  pub fn instruction(&self) -> &Instruction {
+    &self.instruction
+  }
+  /// The instruction `step` will execute next
+  pub fn next_instruction(&self) -> Option<Instruction> {
+    self.instructions.get(self.current_command()).copied()
   }
   #[inline]
   pub fn convert(
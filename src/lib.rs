@@ -1,17 +1,24 @@
 #![deny(clippy::unwrap_used)]
 #![feature(const_mut_refs)]
+#![cfg_attr(test, feature(test))]
 
 use data_struct::IError;
 
-use crate::{
-  data_struct::{Type, TypedByte},
-  utils::convert_kind_byte,
-};
+use crate::{data_struct::Records, interpreter::Interpreter};
 
 pub mod compiler;
+pub mod container;
 pub mod data_struct;
+pub mod diagnostic;
+pub mod disassembler;
+pub mod expression;
 pub mod interpreter;
 pub mod macros;
+pub mod memory_infra;
+pub mod memory_map;
+pub mod parser;
+pub mod runtime_error;
+pub mod source_map;
 
 pub const ROBSON_FOLDER: &str = ".robson_o_grande";
 pub const STDRB_FOLDER: &str = "stdrb";
@@ -19,6 +26,7 @@ pub const STDRB_FOLDER: &str = "stdrb";
 mod tests;
 
 pub mod utils;
+pub mod verifier;
 
 pub trait Infra {
   fn read_line(&mut self) -> Result<String, std::io::Error>;
@@ -47,49 +55,19 @@ pub trait CompilerInfra {
   fn lines(&self, path: &str) -> Result<Vec<String>, IError>;
 }
 
-pub fn print_file_buffer(buffer: Vec<u8>) {
-  let mut command = 0;
-  let mut index = 0;
-  while index < buffer.len() {
-    let opcode = buffer[index];
-    let kind_byte = buffer[index + 1];
-    let param1_byte = [
-      buffer[index + 2],
-      buffer[index + 3],
-      buffer[index + 4],
-      buffer[index + 5],
-    ];
-    let param2_byte = [
-      buffer[index + 6],
-      buffer[index + 7],
-      buffer[index + 8],
-      buffer[index + 9],
-    ];
-    let param3_byte = [
-      buffer[index + 10],
-      buffer[index + 11],
-      buffer[index + 12],
-      buffer[index + 13],
-    ];
+pub fn print_file_buffer(buffer: Vec<u8>) -> Result<(), IError> {
+  let instructions =
+    Interpreter::decode(&buffer).map_err(IError::message)?;
+  let records = Records::new(&buffer).flatten();
+  for (command, (record, instruction)) in
+    records.zip(instructions).enumerate()
+  {
+    let opcode = instruction.opcode;
+    let kind_byte = record.kind_byte;
+    let types = u16::from_le_bytes(record.types_bytes);
+    let [param1, param2, param3] = instruction.params.map(|a| a.0);
 
-    let types = buffer[index + 14];
-    let converted_types = convert_kind_byte(types);
-    let param1 = TypedByte {
-      value: param1_byte,
-      r#type: Type::from(converted_types[0]),
-    };
-    let param2 = TypedByte {
-      value: param2_byte,
-      r#type: Type::from(converted_types[1]),
-    };
-    let param3 = TypedByte {
-      value: param3_byte,
-      r#type: Type::from(converted_types[2]),
-    };
-
-    println!("command: {command}\nopcode: {opcode}\ntypes_byte: {types:08b}\nkind_byte: {kind_byte:08b}\nparam1: {param1}\nparam2: {param2}\nparam3: {param3}\n\n");
-
-    index += 15;
-    command += 1;
+    println!("command: {command}\nopcode: {opcode}\ntypes_bytes: {types:016b}\nkind_byte: {kind_byte:08b}\nparam1: {param1}\nparam2: {param2}\nparam3: {param3}\n\n");
   }
+  Ok(())
 }
@@ -1,18 +1,44 @@
 macro_rules! compiler {
-  ($a:expr, $b:expr) => {
-    match crate::compiler::Compiler::new($a.into(), $b.clone_self()) {
-      Ok(a) => a,
+  ($self:expr, $a:expr, $kind:ident, $line:expr) => {{
+    let origins =
+      $self.origin(crate::diagnostic::OriginKind::$kind, $line);
+    match crate::compiler::Compiler::new(
+      $a.into(),
+      $self.infra.clone_self(),
+    ) {
+      Ok(mut a) => {
+        a.set_origins(origins);
+        a.set_recover($self.recover);
+        a.set_max_errors($self.max_errors);
+        a
+      }
       Err(err) => {
-        if err.to_string().contains("os error 2") {
-          return Err(crate::data_struct::IError::message(&format!(
-            "No such file '{}' (os error 2)",
-            $a
-          )));
+        let message = if err.to_string().contains("os error 2") {
+          format!("No such file '{}' (os error 2)", $a)
         } else {
-          return Err(err);
-        }
+          err.error
+        };
+        return crate::macros::diagnostic!(
+          $self,
+          IncludeFailed,
+          $line,
+          $a,
+          "{}",
+          message
+        );
       }
     }
+  }};
+}
+
+macro_rules! diagnostic {
+  ($self:expr, $code:ident, $line:expr, $token:expr, $($arg:tt)*) => {
+    Err(crate::data_struct::IError::from($self.diagnostic(
+      crate::diagnostic::DiagnosticCode::$code,
+      $line,
+      $token,
+      format!($($arg)*),
+    )))
   };
 }
 
@@ -31,43 +57,75 @@ macro_rules! replace_params {
   ($self:expr, $string:ident) => {
     if $string.contains("$ROBSON") || $string.contains("?ROBSON") {
       if let Some(macro_params) = &mut $self.macro_params {
-        let current = $self.macro_current.top().into();
+        let current = $self.macro_current.top().map_or(0, Into::into);
         let should_pop = $self.macro_current.sx > 0;
         let (str, has_next, is_expr) =
-          crate::utils::convert_macro_robson(
+          match crate::utils::convert_macro_robson(
             $string.to_string(),
             &macro_params,
             current,
-          )?;
+          ) {
+            Ok(a) => a,
+            Err(err) => {
+              return crate::macros::diagnostic!(
+                $self,
+                MacroExpression,
+                $self.pos,
+                "",
+                "{}",
+                err
+              );
+            }
+          };
 
         if is_expr {
           if should_pop {
-            $self.macro_current.pop();
+            let _ = $self.macro_current.pop();
           }
 
           if has_next {
-            $self.macro_current.push((current + 1).into());
+            if $self.macro_current.push((current + 1).into()).is_err()
+            {
+              return crate::macros::diagnostic!(
+                $self,
+                MacroExpression,
+                $self.pos,
+                "",
+                "Too many nested macro expressions"
+              );
+            }
           }
 
           let b = $string.split(" ").collect::<Vec<&str>>();
 
           if b.len() != 2 {
-            return ierror!(
-              "Invalid macro expression at {}",
-              $self.pos
+            return crate::macros::diagnostic!(
+              $self,
+              MacroExpression,
+              $self.pos,
+              "",
+              "Invalid macro expression"
             );
           }
 
           macro_params.insert(b[1].replace("$", "?"), str);
-          if $self.macro_jump.sx != 0 {
-            let x: usize = $self.macro_jump.top().into();
-            if x != $self.pos {
-              $self.macro_jump.push($self.pos.into());
-            } else if !has_next {
-              $self.macro_jump.pop();
+          let pushed = match $self.macro_jump.top() {
+            Ok(x) if Into::<usize>::into(x) == $self.pos => {
+              if !has_next {
+                let _ = $self.macro_jump.pop();
+              }
+              Ok(())
             }
-          } else {
-            $self.macro_jump.push($self.pos.into());
+            _ => $self.macro_jump.push($self.pos.into()),
+          };
+          if pushed.is_err() {
+            return crate::macros::diagnostic!(
+              $self,
+              MacroExpression,
+              $self.pos,
+              "",
+              "Too many nested macro expressions"
+            );
           }
 
           $self.pos += 1;
@@ -81,25 +139,39 @@ macro_rules! replace_params {
 }
 
 macro_rules! sanitize_param {
-  ($self:ident,$string:ident) => {
-    if $string.contains("lambeu") {
-      let result = $self.get_kind_value(&$string);
-      if let Ok((true_value, _, _, _)) = &result {
-        if true_value.r#type != Type::Usigned {
-          return ierror!(
-            "Invalid lambeu at command of line {}",
-            $self.pos - 1
-          );
-        }
-        $string = format!(
-          "comeu {}",
-          crate::utils::u32_from_bytes(true_value.value)
-        )
-      }
-      if let Err(err) = result {
-        if err.error.contains("Cant find") && $self.is_preload {
-          $string = format!("comeu 1");
-        }
+  ($self:ident, $string:ident, $line:expr) => {
+    let tokens = crate::parser::tokenize($line, &$string);
+    if let Ok(param) = crate::parser::Param::parse(&tokens) {
+      let is_lambeu = param.kind == crate::parser::ParamKind::Lambeu;
+      let convert = if param.convert { " robson" } else { "" };
+      match $self.param_expr(&param, $line) {
+        Ok(mut expr) => match expr.eval(&|name| $self.label(name)) {
+          Ok(value) if is_lambeu => {
+            $string = format!("comeu {}", value.force_u32())
+          }
+          Ok(value)
+            if crate::expression::literal(&param.value.text)
+              .is_err() =>
+          {
+            // expressions use the constants of this file
+            $string =
+              format!("{} {}{}", param.kind.name(), value, convert);
+          }
+          Err(crate::expression::ExprError::UndefinedLabel(_)) => {
+            // patched by this file once the macro is compiled, as
+            // the var addresses once the vars are placed
+            expr.labels_mut(&mut |name| {
+              if name != crate::compiler::DATA_LABEL {
+                name.insert(0, '^')
+              }
+            });
+            $string =
+              format!("{} {}{}", param.kind.name(), expr, convert);
+          }
+          _ => {}
+        },
+        Err(err) if is_lambeu => return Err(err),
+        Err(_) => {}
       }
     }
   };
@@ -111,53 +183,112 @@ macro_rules! force_u32 {
   };
 }
 
+macro_rules! stack_error {
+  ($self:ident, $err:expr) => {
+    $self.err = match $err {
+      crate::data_struct::StackError::Overflow => {
+        crate::macros::runtime_error!($self, StackOverflow)
+      }
+      crate::data_struct::StackError::Underflow => {
+        crate::macros::runtime_error!($self, StackUnderflow)
+      }
+    }
+  };
+}
+
+macro_rules! cell {
+  ($self:ident, $address:expr) => {
+    match $self.cell($address) {
+      Some(a) => &mut $self.memory[a],
+      None => return,
+    }
+  };
+}
+
 macro_rules! top {
+  ($self:ident) => {
+    match $self.stack.top() {
+      Ok(a) => a,
+      Err(err) => {
+        crate::macros::stack_error!($self, err);
+        return;
+      }
+    }
+  };
+}
+
+macro_rules! pop {
+  ($self:ident) => {
+    match $self.stack.pop() {
+      Ok(a) => a,
+      Err(err) => {
+        crate::macros::stack_error!($self, err);
+        return;
+      }
+    }
+  };
+}
+
+macro_rules! push {
   ($self:ident, $expr:expr) => {
-    $expr.top()
+    if let Err(err) = $self.stack.push($expr) {
+      crate::macros::stack_error!($self, err);
+      return;
+    }
   };
 }
 
 macro_rules! convert {
   ($self:ident, $ident:ident) => {
+    // the conversions set the error when failing
     if !$self.convert(&mut $ident.0, $ident.1) {
-        $self.err = Some(crate::data_struct::IError::message(&format!(
-          "Failed to convert expression of kind {} at the command '{}'",
-          $ident.1, $self.current_command()
-        )));
-        return;
+      return;
     }
   };
 }
 
-macro_rules! someierror {
-  ($arg:literal) => {
-    Some(crate::data_struct::IError::message(&$arg))
+macro_rules! runtime_error {
+  ($self:expr, $variant:ident) => {
+    Some(crate::runtime_error::RuntimeError::$variant {
+      context: $self.error_context(),
+    })
+  };
+  ($self:expr, $variant:ident { $($field:ident: $value:expr),* $(,)? }) => {
+    Some(crate::runtime_error::RuntimeError::$variant {
+      context: $self.error_context(),
+      $($field: $value),*
+    })
   };
-($($arg:tt)*) => {
-    Some(crate::data_struct::IError::message(
-    &format!($($arg)*)
-    ))
-};
 }
 
 macro_rules! try_err {
-  ($self:ident, $expr:expr) => {
+  ($self:ident, $variant:ident, $expr:expr) => {
     match $expr {
       Ok(a) => a,
       Err(err) => {
-        $self.err = Some(err.into());
+        $self.err = crate::macros::runtime_error!(
+          $self,
+          $variant {
+            message: err.to_string()
+          }
+        );
         return;
       }
     }
   };
 }
 
+pub(crate) use cell;
 pub(crate) use compiler;
 pub(crate) use convert;
+pub(crate) use diagnostic;
 pub(crate) use force_u32;
 pub(crate) use ierror;
+pub(crate) use pop;
+pub(crate) use push;
 pub(crate) use replace_params;
+pub(crate) use runtime_error;
 pub(crate) use sanitize_param;
-pub(crate) use someierror;
+pub(crate) use stack_error;
 pub(crate) use top;
 pub(crate) use try_err;
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use crate::{data_struct::IError, CompilerInfra, ROBSON_FOLDER};
+
+/// A `CompilerInfra` over in-memory files, for compiling without a
+/// filesystem. Files added as `stdrb/<name>` answer `stdrb/` includes
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryCompilerInfra {
+  files: HashMap<String, String>,
+  output: String,
+}
+impl MemoryCompilerInfra {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn file(mut self, path: &str, source: &str) -> Self {
+    self.add_file(path, source);
+    self
+  }
+  /// Replaces the file at `path` if there is one
+  pub fn add_file(&mut self, path: &str, source: &str) {
+    self.files.insert(normalize(path), source.to_owned());
+  }
+
+  /// Everything printed while compiling
+  pub fn output(&self) -> &str {
+    &self.output
+  }
+}
+
+/// The compiler resolves `stdrb/a.robson` to
+/// `<home>/.robson_o_grande/stdrb/a.robson`, the home here is empty
+fn normalize(path: &str) -> String {
+  let path = path.trim_start_matches("./");
+  path
+    .strip_prefix(&format!("{ROBSON_FOLDER}/"))
+    .unwrap_or(path)
+    .to_owned()
+}
+
+impl CompilerInfra for MemoryCompilerInfra {
+  fn clone_self(&mut self) -> Box<dyn CompilerInfra> {
+    Box::new(Self {
+      files: self.files.clone(),
+      output: String::new(),
+    })
+  }
+  fn color_print(&mut self, to_print: String, _: u64) {
+    self.output.push_str(&to_print);
+  }
+  fn println(&mut self, to_print: String) {
+    self.output.push_str(&format!("{to_print}\n"));
+  }
+
+  fn home_dir(&self) -> Option<String> {
+    Some(String::new())
+  }
+  fn lines(&self, path: &str) -> Result<Vec<String>, IError> {
+    match self.files.get(&normalize(path)) {
+      Some(source) => Ok(source.lines().map(str::to_owned).collect()),
+      None => Err(IError::message(format!("No such file '{path}'"))),
+    }
+  }
+}
@@ -0,0 +1,63 @@
+use std::fmt::Display;
+
+use crate::data_struct::Type;
+
+/// A memory cell range given to a `var`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+  pub name: String,
+  pub address: usize,
+  /// In cells, a cell holds any type
+  pub size: usize,
+  /// `None` for arrays
+  pub r#type: Option<Type>,
+  pub path: String,
+  /// 1-based line of the declaration
+  pub line: usize,
+}
+
+/// The variables of every compiled file, in allocation order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryMap {
+  pub variables: Vec<Variable>,
+}
+impl MemoryMap {
+  /// The variable holding `address`
+  pub fn get(&self, address: usize) -> Option<&Variable> {
+    self
+      .variables
+      .iter()
+      .find(|a| (a.address..a.address + a.size).contains(&address))
+  }
+  /// The first address after every variable
+  pub fn end(&self) -> usize {
+    self
+      .variables
+      .iter()
+      .map(|a| a.address + a.size)
+      .max()
+      .unwrap_or_default()
+  }
+}
+impl Display for MemoryMap {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "address  size  type  name")?;
+    for variable in &self.variables {
+      let r#type = match variable.r#type {
+        Some(r#type) => r#type.name().to_owned(),
+        None => format!("[{}]", variable.size),
+      };
+      writeln!(
+        f,
+        "{:<8} {:<5} {:<5} {} ({}:{})",
+        variable.address,
+        variable.size,
+        r#type,
+        variable.name,
+        variable.path,
+        variable.line
+      )?;
+    }
+    Ok(())
+  }
+}
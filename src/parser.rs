@@ -0,0 +1,476 @@
+use std::ops::Range;
+
+use crate::{
+  data_struct::Type, diagnostic::DiagnosticCode, expression::is_name,
+};
+
+/// Where a token sits, a 0-based line and a byte range inside it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+  pub line: usize,
+  pub start: usize,
+  pub end: usize,
+}
+impl Span {
+  /// 1-based char columns of the span in `source`, end exclusive
+  pub fn columns(&self, source: &str) -> Range<usize> {
+    let start = source[..self.start].chars().count() + 1;
+    start..start + source[self.start..self.end].chars().count().max(1)
+  }
+  pub fn to(&self, other: Span) -> Span {
+    Span {
+      end: other.end,
+      ..*self
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+  pub text: String,
+  pub span: Span,
+}
+
+/// Splits a line at spaces and tabs. A `;` outside quotes comments
+/// out the rest of the line and a token starting with a quote runs
+/// until the closing quote, spaces, `;` and escaped quotes included
+pub fn tokenize(line: usize, source: &str) -> Vec<Token> {
+  let mut tokens = Vec::new();
+  let mut chars = source.char_indices().peekable();
+  while let Some(&(start, char)) = chars.peek() {
+    if char.is_whitespace() {
+      chars.next();
+      continue;
+    }
+    if char == ';' {
+      break;
+    }
+    let mut end = start;
+    let mut quote = matches!(char, '\'' | '"').then_some(char);
+    let mut escaped = false;
+    chars.next();
+    end += char.len_utf8();
+    while let Some(&(index, next)) = chars.peek() {
+      match quote {
+        Some(_) if escaped => escaped = false,
+        Some(_) if next == '\\' => escaped = true,
+        Some(a) if next == a => quote = None,
+        Some(_) => {}
+        None if next.is_whitespace() || next == ';' => break,
+        None => {}
+      }
+      chars.next();
+      end = index + next.len_utf8();
+    }
+    tokens.push(Token {
+      text: source[start..end].to_owned(),
+      span: Span { line, start, end },
+    });
+  }
+  tokens
+}
+
+/// Replaces `\n`, `\t`, `\r`, `\0`, `\\`, `\'`, `\"` and
+/// `\u{hex}` by the char they stand for
+pub fn unescape(text: &str) -> Result<String, String> {
+  let mut string = String::new();
+  let mut chars = text.chars();
+  while let Some(char) = chars.next() {
+    if char != '\\' {
+      string.push(char);
+      continue;
+    }
+    string.push(match chars.next() {
+      Some('n') => '\n',
+      Some('t') => '\t',
+      Some('r') => '\r',
+      Some('0') => '\0',
+      Some(a @ ('\\' | '\'' | '"')) => a,
+      Some('u') => {
+        let rest = chars.as_str();
+        let code = rest
+          .strip_prefix('{')
+          .and_then(|a| a.split_once('}'))
+          .map(|a| a.0)
+          .ok_or("Malformated escape, expected '\\u{hex}'")?;
+        chars = rest[code.len() + 2..].chars();
+        u32::from_str_radix(code, 16)
+          .ok()
+          .and_then(char::from_u32)
+          .ok_or(format!("Invalid unicode escape '\\u{{{code}}}'"))?
+      }
+      Some(a) => return Err(format!("Unknown escape '\\{a}'")),
+      None => return Err("Unfinished escape".to_owned()),
+    });
+  }
+  Ok(string)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParamKind {
+  Comeu,
+  Chupou,
+  Fudeu,
+  Penetrou,
+  Lambeu,
+}
+impl ParamKind {
+  pub fn from_name(name: &str) -> Option<Self> {
+    Some(match name {
+      "comeu" => Self::Comeu,
+      "chupou" => Self::Chupou,
+      "fudeu" => Self::Fudeu,
+      "penetrou" => Self::Penetrou,
+      "lambeu" => Self::Lambeu,
+      _ => return None,
+    })
+  }
+  pub const fn name(&self) -> &'static str {
+    match self {
+      Self::Comeu => "comeu",
+      Self::Chupou => "chupou",
+      Self::Fudeu => "fudeu",
+      Self::Penetrou => "penetrou",
+      Self::Lambeu => "lambeu",
+    }
+  }
+  /// The 2 bits of the kind byte, a label is a constant
+  pub const fn bits(&self) -> u8 {
+    match self {
+      Self::Comeu | Self::Lambeu => 0,
+      Self::Chupou => 1,
+      Self::Fudeu => 2,
+      Self::Penetrou => 3,
+    }
+  }
+}
+
+/// `<kind> <value> [robson]`, the value can be an expression spread
+/// over many tokens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Param {
+  pub kind: ParamKind,
+  pub value: Token,
+  pub convert: bool,
+}
+impl Param {
+  pub fn parse(tokens: &[Token]) -> Result<Self, SyntaxError> {
+    let (Some(first), Some(last)) = (tokens.first(), tokens.last())
+    else {
+      return Err(SyntaxError::new(
+        DiagnosticCode::MissingParam,
+        Span::default(),
+        "",
+        "Missing param",
+      ));
+    };
+    let convert = tokens.len() > 2 && last.text == "robson";
+    let value = &tokens[1..tokens.len() - convert as usize];
+    let (Some(start), Some(end)) = (value.first(), value.last())
+    else {
+      return Err(SyntaxError::new(
+        DiagnosticCode::MalformedParam,
+        first.span.to(last.span),
+        &join(tokens),
+        "Malformated param, expected '<kind> <value> [robson]'",
+      ));
+    };
+    let Some(kind) = ParamKind::from_name(&first.text) else {
+      return Err(SyntaxError::new(
+        DiagnosticCode::UnknownParam,
+        first.span,
+        &first.text,
+        format!("Unexpect token for param '{}'", first.text),
+      ));
+    };
+    Ok(Self {
+      kind,
+      value: Token {
+        text: join(value),
+        span: start.span.to(end.span),
+      },
+      convert,
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Directive {
+  /// `SEMPRE#ROBSON`, back to the macro expression being expanded
+  Sempre,
+  /// `PARE#ROBSON`, ends the innermost macro expression
+  Pare,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Declaration {
+  /// `<type> [= <expression>]`, zeroed without a value
+  Scalar { r#type: Type, value: Option<String> },
+  /// `[<size>] [= "text"]`, the text with the escapes replaced
+  Array { size: String, text: Option<String> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Statement {
+  Empty,
+  Label(String),
+  /// The number of `robson` words
+  Opcode(usize),
+  /// `robsons <path>`
+  Include(String),
+  /// `[robsons <path>]`, followed by a line for each macro param
+  Macro(String),
+  Directive(Directive),
+  /// `const <name> = <expression>`
+  Const {
+    name: String,
+    value: String,
+  },
+  /// `string [address] "text"`, with the escapes replaced
+  String {
+    address: Option<String>,
+    text: String,
+  },
+  /// `var <name>: <declaration>`
+  Var {
+    name: String,
+    declaration: Declaration,
+  },
+  /// Uses `$ROBSON` or `?ROBSON` params, parsed again once they are
+  /// replaced
+  Placeholder,
+  /// Any other line, a param or a push abbreviation
+  Param,
+}
+
+/// One line of source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node {
+  pub statement: Statement,
+  pub tokens: Vec<Token>,
+}
+impl Node {
+  /// The tokens separated by single spaces, without the comment
+  pub fn text(&self) -> String {
+    join(&self.tokens)
+  }
+  pub fn span(&self) -> Option<Span> {
+    Some(self.tokens.first()?.span.to(self.tokens.last()?.span))
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+  pub code: DiagnosticCode,
+  pub span: Span,
+  pub token: String,
+  pub message: String,
+}
+impl SyntaxError {
+  fn new(
+    code: DiagnosticCode,
+    span: Span,
+    token: &str,
+    message: impl Into<String>,
+  ) -> Self {
+    Self {
+      code,
+      span,
+      token: token.to_owned(),
+      message: message.into(),
+    }
+  }
+}
+
+fn join(tokens: &[Token]) -> String {
+  tokens
+    .iter()
+    .map(|a| a.text.as_str())
+    .collect::<Vec<&str>>()
+    .join(" ")
+}
+
+/// Parses the 0-based `line`, the compiler decides what a param line
+/// means from the statement before it
+pub fn parse_line(
+  line: usize,
+  source: &str,
+) -> Result<Node, SyntaxError> {
+  let tokens = tokenize(line, source);
+  let statement = statement(&tokens)?;
+  Ok(Node { statement, tokens })
+}
+
+pub fn parse(lines: &[String]) -> Vec<Result<Node, SyntaxError>> {
+  lines
+    .iter()
+    .enumerate()
+    .map(|(line, source)| parse_line(line, source))
+    .collect()
+}
+
+fn statement(tokens: &[Token]) -> Result<Statement, SyntaxError> {
+  let (Some(first), Some(last)) = (tokens.first(), tokens.last())
+  else {
+    return Ok(Statement::Empty);
+  };
+  let span = first.span.to(last.span);
+  if tokens
+    .iter()
+    .any(|a| a.text.contains("$ROBSON") || a.text.contains("?ROBSON"))
+  {
+    return Ok(Statement::Placeholder);
+  }
+
+  match first.text.as_str() {
+    "SEMPRE#ROBSON" if tokens.len() == 1 => {
+      Ok(Statement::Directive(Directive::Sempre))
+    }
+    "PARE#ROBSON" if tokens.len() == 1 => {
+      Ok(Statement::Directive(Directive::Pare))
+    }
+    "robsons" => match tokens {
+      [_, path] => Ok(Statement::Include(path.text.clone())),
+      _ => Err(SyntaxError::new(
+        DiagnosticCode::MalformedInclude,
+        span,
+        &join(tokens),
+        "Malformated robsons, expected 'robsons <path>'",
+      )),
+    },
+    "robson" => match tokens.iter().find(|a| a.text != "robson") {
+      Some(token) => Err(SyntaxError::new(
+        DiagnosticCode::InvalidToken,
+        token.span,
+        &token.text,
+        format!("Invalid token for opcode '{}'", token.text),
+      )),
+      None => Ok(Statement::Opcode(tokens.len())),
+    },
+    "const" => {
+      let text = join(&tokens[1..]);
+      match text.split_once('=') {
+        Some((name, value))
+          if is_name(name.trim()) && !value.trim().is_empty() =>
+        {
+          Ok(Statement::Const {
+            name: name.trim().to_owned(),
+            value: value.trim().to_owned(),
+          })
+        }
+        _ => Err(SyntaxError::new(
+          DiagnosticCode::MalformedConstant,
+          span,
+          &join(tokens),
+          "Malformated const, expected 'const <name> = <value>'",
+        )),
+      }
+    }
+    "string" => {
+      let malformed = |message: String| {
+        SyntaxError::new(
+          DiagnosticCode::MalformedString,
+          span,
+          &join(tokens),
+          message,
+        )
+      };
+      let [_, address @ .., text] = tokens else {
+        return Err(malformed(
+          "Malformated string, expected 'string [address] \"text\"'"
+            .to_owned(),
+        ));
+      };
+      let Some(inner) = text
+        .text
+        .strip_prefix('"')
+        .and_then(|a| a.strip_suffix('"'))
+      else {
+        return Err(malformed(format!(
+          "Malformated string {}, expected '\"text\"'",
+          text.text
+        )));
+      };
+      Ok(Statement::String {
+        address: (!address.is_empty()).then(|| join(address)),
+        text: unescape(inner).map_err(malformed)?,
+      })
+    }
+    "var" => {
+      let malformed = || {
+        SyntaxError::new(
+          DiagnosticCode::MalformedVariable,
+          span,
+          &join(tokens),
+          "Malformated var, expected 'var <name>: <type> [= <value>]' \
+           or 'var <name>: [<size>] [= \"text\"]'",
+        )
+      };
+      let text = join(&tokens[1..]);
+      let (name, rest) =
+        text.split_once(':').ok_or_else(malformed)?;
+      let (r#type, value) = match rest.split_once('=') {
+        Some((r#type, value)) => (r#type.trim(), Some(value.trim())),
+        None => (rest.trim(), None),
+      };
+      if !is_name(name.trim()) {
+        return Err(malformed());
+      }
+      let declaration = match r#type
+        .strip_prefix('[')
+        .and_then(|a| a.strip_suffix(']'))
+      {
+        Some(size) => Declaration::Array {
+          size: size.trim().to_owned(),
+          text: match value {
+            Some(value) => {
+              let inner = value
+                .strip_prefix('"')
+                .and_then(|a| a.strip_suffix('"'))
+                .ok_or_else(malformed)?;
+              Some(unescape(inner).map_err(|err| {
+                SyntaxError::new(
+                  DiagnosticCode::MalformedString,
+                  span,
+                  value,
+                  err,
+                )
+              })?)
+            }
+            None => None,
+          },
+        },
+        None => Declaration::Scalar {
+          r#type: Type::from_name(r#type).ok_or_else(malformed)?,
+          value: value.map(str::to_owned),
+        },
+      };
+      Ok(Statement::Var {
+        name: name.trim().to_owned(),
+        declaration,
+      })
+    }
+    text if text.starts_with('[') => {
+      let text = join(tokens);
+      let inner = text
+        .strip_prefix('[')
+        .and_then(|a| a.strip_suffix(']'))
+        .unwrap_or_default();
+      match inner.split_whitespace().collect::<Vec<&str>>()[..] {
+        ["robsons", path] if !path.contains(['[', ']']) => {
+          Ok(Statement::Macro(path.to_owned()))
+        }
+        _ => Err(SyntaxError::new(
+          DiagnosticCode::MalformedMacro,
+          span,
+          &text,
+          "Malformated robsons macro",
+        )),
+      }
+    }
+    text if tokens.len() == 1 && text.ends_with(':') => {
+      Ok(Statement::Label(text.trim_end_matches(':').to_owned()))
+    }
+    _ => Ok(Statement::Param),
+  }
+}
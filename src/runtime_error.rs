@@ -0,0 +1,219 @@
+use std::{error::Error, fmt::Display};
+
+use crate::{
+  data_struct::{IError, Type, TypedByte},
+  source_map::SourceLocation,
+  verifier::Problem,
+};
+
+/// How many values from the top of the stack an error keeps
+pub const STACK_VIEW: usize = 8;
+
+/// Where the interpreter was when it failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorContext {
+  pub command: usize,
+  pub opcode: u8,
+  /// Up to `STACK_VIEW` values, the top of the stack last
+  pub stack: Vec<TypedByte>,
+  pub location: Option<SourceLocation>,
+}
+impl Display for ErrorContext {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "at command {} (opcode {})",
+      self.command, self.opcode
+    )?;
+    let stack = self
+      .stack
+      .iter()
+      .map(|a| a.to_string())
+      .collect::<Vec<String>>()
+      .join(", ");
+    write!(f, "\n  stack top: [{stack}]")?;
+    if let Some(location) = &self.location {
+      write!(f, "\n  --> {location}")?;
+    }
+    Ok(())
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeError {
+  StackUnderflow {
+    context: ErrorContext,
+  },
+  StackOverflow {
+    context: ErrorContext,
+  },
+  CallStackUnderflow {
+    context: ErrorContext,
+  },
+  CallStackOverflow {
+    context: ErrorContext,
+  },
+  InvalidOpcode {
+    context: ErrorContext,
+  },
+  TypeMismatch {
+    context: ErrorContext,
+    left: Type,
+    right: Type,
+  },
+  DivisionByZero {
+    context: ErrorContext,
+  },
+  InvalidOperation {
+    context: ErrorContext,
+    kind: u32,
+    r#type: Type,
+  },
+  Overflow {
+    context: ErrorContext,
+  },
+  MemoryOutOfRange {
+    context: ErrorContext,
+    address: usize,
+  },
+  Io {
+    context: ErrorContext,
+    message: String,
+  },
+  Terminal {
+    context: ErrorContext,
+    message: String,
+  },
+  Unverified {
+    context: ErrorContext,
+    problems: Vec<Problem>,
+  },
+  /// The instruction was not executed, add fuel and run again
+  OutOfFuel {
+    context: ErrorContext,
+  },
+  /// The instruction was not executed, move the deadline and run
+  /// again
+  DeadlineExceeded {
+    context: ErrorContext,
+  },
+  /// The input opcode was not executed, call `provide_input` and run
+  /// again
+  WaitingForInput {
+    context: ErrorContext,
+  },
+}
+impl RuntimeError {
+  /// Whether running again can continue the program
+  pub fn is_resumable(&self) -> bool {
+    matches!(
+      self,
+      RuntimeError::OutOfFuel { .. }
+        | RuntimeError::DeadlineExceeded { .. }
+        | RuntimeError::WaitingForInput { .. }
+    )
+  }
+  pub fn context(&self) -> &ErrorContext {
+    match self {
+      RuntimeError::StackUnderflow { context }
+      | RuntimeError::StackOverflow { context }
+      | RuntimeError::CallStackUnderflow { context }
+      | RuntimeError::CallStackOverflow { context }
+      | RuntimeError::InvalidOpcode { context }
+      | RuntimeError::TypeMismatch { context, .. }
+      | RuntimeError::DivisionByZero { context }
+      | RuntimeError::InvalidOperation { context, .. }
+      | RuntimeError::Overflow { context }
+      | RuntimeError::MemoryOutOfRange { context, .. }
+      | RuntimeError::Io { context, .. }
+      | RuntimeError::Terminal { context, .. }
+      | RuntimeError::Unverified { context, .. }
+      | RuntimeError::OutOfFuel { context }
+      | RuntimeError::DeadlineExceeded { context }
+      | RuntimeError::WaitingForInput { context } => context,
+    }
+  }
+  pub fn context_mut(&mut self) -> &mut ErrorContext {
+    match self {
+      RuntimeError::StackUnderflow { context }
+      | RuntimeError::StackOverflow { context }
+      | RuntimeError::CallStackUnderflow { context }
+      | RuntimeError::CallStackOverflow { context }
+      | RuntimeError::InvalidOpcode { context }
+      | RuntimeError::TypeMismatch { context, .. }
+      | RuntimeError::DivisionByZero { context }
+      | RuntimeError::InvalidOperation { context, .. }
+      | RuntimeError::Overflow { context }
+      | RuntimeError::MemoryOutOfRange { context, .. }
+      | RuntimeError::Io { context, .. }
+      | RuntimeError::Terminal { context, .. }
+      | RuntimeError::Unverified { context, .. }
+      | RuntimeError::OutOfFuel { context }
+      | RuntimeError::DeadlineExceeded { context }
+      | RuntimeError::WaitingForInput { context } => context,
+    }
+  }
+}
+impl Display for RuntimeError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      RuntimeError::StackUnderflow { .. } => {
+        write!(f, "Trying to use the stack while empty")?
+      }
+      RuntimeError::StackOverflow { .. } => {
+        write!(f, "Stack overflow")?
+      }
+      RuntimeError::CallStackUnderflow { .. } => {
+        write!(f, "Returning without a call")?
+      }
+      RuntimeError::CallStackOverflow { .. } => {
+        write!(f, "Call stack overflow")?
+      }
+      RuntimeError::InvalidOpcode { context } => {
+        write!(f, "Invalid opcode {}", context.opcode)?
+      }
+      RuntimeError::TypeMismatch { left, right, .. } => write!(
+        f,
+        "Comparing incompatible types {left:?} and {right:?}"
+      )?,
+      RuntimeError::DivisionByZero { .. } => {
+        write!(f, "Division by zero")?
+      }
+      RuntimeError::InvalidOperation { kind, r#type, .. } => {
+        write!(f, "Invalid operation kind {kind} for {type:?}")?
+      }
+      RuntimeError::Overflow { .. } => {
+        write!(f, "Arithmetic overflow")?
+      }
+      RuntimeError::MemoryOutOfRange { address, .. } => {
+        write!(f, "Memory address {address} is out of range")?
+      }
+      RuntimeError::Io { message, .. } => {
+        write!(f, "I/O failure: {message}")?
+      }
+      RuntimeError::Terminal { message, .. } => {
+        write!(f, "Terminal failure: {message}")?
+      }
+      RuntimeError::Unverified { problems, .. } => {
+        write!(f, "Bytecode verification failed")?;
+        for problem in problems {
+          write!(f, "\n  {problem}")?;
+        }
+      }
+      RuntimeError::OutOfFuel { .. } => write!(f, "Out of fuel")?,
+      RuntimeError::DeadlineExceeded { .. } => {
+        write!(f, "Deadline exceeded")?
+      }
+      RuntimeError::WaitingForInput { .. } => {
+        write!(f, "Waiting for input")?
+      }
+    }
+    write!(f, " {}", self.context())
+  }
+}
+impl Error for RuntimeError {}
+impl From<RuntimeError> for IError {
+  fn from(err: RuntimeError) -> Self {
+    IError::message(err)
+  }
+}
@@ -0,0 +1,96 @@
+use std::fmt::Display;
+
+use crate::{
+  container::{push_string, Reader},
+  data_struct::IError,
+  diagnostic::{Origin, OriginKind},
+};
+
+/// Where a compiled instruction came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+  pub path: String,
+  /// 1-based line of the opcode
+  pub line: usize,
+  /// Includes and macro expansions that led here, outermost first
+  pub origins: Vec<Origin>,
+}
+impl Display for SourceLocation {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}:{}", self.path, self.line)?;
+    for origin in self.origins.iter().rev() {
+      write!(f, "\n  {origin}")?;
+    }
+    Ok(())
+  }
+}
+
+/// Instruction index to source location, emitted by the compiler
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SourceMap {
+  pub locations: Vec<SourceLocation>,
+}
+impl SourceMap {
+  pub fn get(&self, command: usize) -> Option<&SourceLocation> {
+    self.locations.get(command)
+  }
+
+  /// The first instruction at `line` of `path`, or at the closest
+  /// line after it. `path` may be a suffix such as `file.robson`
+  pub fn find_line(&self, path: &str, line: usize) -> Option<usize> {
+    self
+      .locations
+      .iter()
+      .enumerate()
+      .filter(|(_, a)| {
+        a.line >= line
+          && (a.path == path || a.path.ends_with(&format!("/{path}")))
+      })
+      .min_by_key(|(command, a)| (a.line, *command))
+      .map(|(command, _)| command)
+  }
+
+  pub fn to_bytes(&self) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend((self.locations.len() as u32).to_le_bytes());
+    for location in &self.locations {
+      push_string(&mut out, &location.path);
+      out.extend((location.line as u32).to_le_bytes());
+      out.extend((location.origins.len() as u32).to_le_bytes());
+      for origin in &location.origins {
+        out.extend((origin.kind as u32).to_le_bytes());
+        push_string(&mut out, &origin.path);
+        out.extend((origin.line as u32).to_le_bytes());
+      }
+    }
+    out
+  }
+
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, IError> {
+    let mut reader = Reader::new(bytes);
+    let count = reader.u32()?;
+    let mut locations = Vec::new();
+    for _ in 0..count {
+      let path = reader.string()?;
+      let line = reader.u32()? as usize;
+      let mut origins = Vec::new();
+      for _ in 0..reader.u32()? {
+        let kind = match reader.u32()? {
+          0 => OriginKind::Include,
+          _ => OriginKind::Macro,
+        };
+        origins.push(Origin {
+          kind,
+          path: reader.string()?,
+          line: reader.u32()? as usize,
+        });
+      }
+      locations.push(SourceLocation {
+        path,
+        line,
+        origins,
+      });
+    }
+    Ok(Self { locations })
+  }
+}
@@ -1,11 +1,35 @@
+extern crate test;
+
 use std::{
   collections::HashMap,
   io::{BufRead, BufReader},
+  time::{Duration, Instant},
 };
 
 use crate::{
-  compiler::Compiler, data_struct::IError, interpreter::Interpreter,
-  utils::convert_macro_robson, CompilerInfra, Infra,
+  compiler::Compiler,
+  container::{Container, FORMAT_VERSION},
+  data_struct::{
+    AddressPolicy,
+    Arithmetic::{self, Saturating, Trap},
+    IError, Stack, StackError, Type, TypedByte, HEADER_SIZE,
+    RECORD_SIZE,
+  },
+  diagnostic::{DiagnosticCode, OriginKind},
+  disassembler::disassemble,
+  expression::{Expr, Operator},
+  interpreter::{
+    compare, ExecutionState, Interpreter, COMPARISONS,
+    DEADLINE_INTERVAL, OPERATIONS,
+  },
+  memory_infra::MemoryCompilerInfra,
+  parser::{parse_line, tokenize, Statement},
+  runtime_error::RuntimeError,
+  utils::{
+    convert_macro_robson, create_types_bytes, i32_shr, u32_shl,
+  },
+  verifier::{verify, ProblemKind},
+  CompilerInfra, Infra,
 };
 
 pub struct TestInfra {
@@ -132,7 +156,8 @@ fn push_and_print() {
   let compiled = compiler.compile().unwrap();
   let mut infra = TestInfra::new("12\ntesteteste123".to_owned());
 
-  let mut interpreter = Interpreter::<10>::new(&compiled);
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
   interpreter.run_buffer(&mut infra).unwrap()
 }
 #[test]
@@ -145,7 +170,8 @@ fn jump() {
   let compiled = compiler.compile().unwrap();
   let mut infra = TestInfra::new("12\ntesteteste123".to_owned());
 
-  let mut interpreter = Interpreter::<10>::new(&compiled);
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
   interpreter.run_buffer(&mut infra).unwrap()
 }
 
@@ -159,7 +185,8 @@ fn memory() {
   let mut infra = TestInfra::new("12\ntesteteste123".to_owned());
 
   let compiled = compiler.compile().unwrap();
-  let mut interpreter = Interpreter::<10>::new(&compiled);
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
   interpreter.run_buffer(&mut infra).unwrap()
 }
 
@@ -173,7 +200,8 @@ fn if_() {
   let compiled = compiler.compile().unwrap();
   let mut infra = TestInfra::new("12\ntesteteste123".to_owned());
 
-  let mut interpreter = Interpreter::<10>::new(&compiled);
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
   interpreter.run_buffer(&mut infra).unwrap()
 }
 #[test]
@@ -185,7 +213,8 @@ fn input() {
   .unwrap();
   let mut infra = TestInfra::new("12\ntesteteste123".to_owned());
   let compiled = compiler.compile().unwrap();
-  let mut interpreter = Interpreter::<10>::new(&compiled);
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
   interpreter.run_buffer(&mut infra).unwrap()
 }
 #[test]
@@ -199,7 +228,8 @@ fn operations() {
 
   let mut infra = TestInfra::new("12\ntesteteste123".to_owned());
 
-  let mut interpreter = Interpreter::<10>::new(&compiled);
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
   interpreter.run_buffer(&mut infra).unwrap()
 }
 
@@ -213,7 +243,8 @@ fn types() {
   let mut infra = TestInfra::new("12\ntesteteste123".to_owned());
 
   let compiled = compiler.compile().unwrap();
-  let mut interpreter = Interpreter::<10>::new(&compiled);
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
   interpreter.run_buffer(&mut infra).unwrap()
 }
 
@@ -227,10 +258,56 @@ fn include() {
   let compiled = compiler.compile().unwrap();
   let mut infra = TestInfra::new("12\ntesteteste123".to_owned());
 
-  let mut interpreter = Interpreter::<10>::new(&compiled);
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
   interpreter.run_buffer(&mut infra).unwrap()
 }
 
+#[test]
+fn from_source() {
+  let mut files = MemoryCompilerInfra::new();
+  for name in ["include", "if", "jump"] {
+    let path = format!("tests/{name}.robson");
+    files.add_file(&path, &std::fs::read_to_string(&path).unwrap());
+  }
+  let source =
+    files.lines("tests/include.robson").unwrap().join("\n");
+  let mut compiler = Compiler::from_source(
+    "tests/include.robson".to_owned(),
+    &source,
+    Box::new(files),
+  );
+  let mut on_disk = Compiler::new(
+    "tests/include.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  assert_eq!(compiler.compile().unwrap(), on_disk.compile().unwrap());
+
+  let files = MemoryCompilerInfra::new().file(
+    "stdrb/seven.robson",
+    "robson robson robson\ncomeu 7\nrobson robson robson robson \
+     robson robson robson robson",
+  );
+  let mut compiler = Compiler::from_source(
+    "main.robson".to_owned(),
+    "robsons stdrb/seven.robson\n",
+    Box::new(files.clone()),
+  );
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  Interpreter::new(&compiled).run_buffer(&mut infra).unwrap();
+  assert_eq!(infra.stdout, "7");
+
+  let mut compiler = Compiler::from_source(
+    "main.robson".to_owned(),
+    "robsons missing.robson\n",
+    Box::new(files),
+  );
+  let err = compiler.compile().unwrap_err();
+  assert!(err.to_string().contains("No such file 'missing.robson'"));
+}
+
 #[test]
 fn multiplelambeu() {
   let mut compiler = Compiler::new(
@@ -241,7 +318,8 @@ fn multiplelambeu() {
   let compiled = compiler.compile().unwrap();
   let mut infra = TestInfra::new("12\ntesteteste123".to_owned());
 
-  let mut interpreter = Interpreter::<10>::new(&compiled);
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
   interpreter.run_buffer(&mut infra).unwrap()
 }
 
@@ -281,6 +359,1217 @@ fn thousand() {
   let compiled = compiler.compile().unwrap();
   let mut infra = TestInfra::new("".to_owned());
 
-  let mut interpreter = Interpreter::<10>::new(&compiled);
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
   interpreter.run_buffer(&mut infra).unwrap()
 }
+
+#[test]
+fn diagnostic() {
+  let mut compiler = Compiler::new(
+    "tests/diagnostic.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let err = compiler.compile().unwrap_err();
+  let diagnostic = &err.diagnostics[0];
+
+  assert_eq!(diagnostic.code, DiagnosticCode::UnknownParam);
+  assert_eq!(diagnostic.path, "tests/wrongparam.robson");
+  assert_eq!(diagnostic.line, 5);
+  assert_eq!(diagnostic.columns, 1..7);
+  assert_eq!(diagnostic.chain.len(), 1);
+  assert_eq!(diagnostic.chain[0].kind, OriginKind::Include);
+  assert_eq!(diagnostic.chain[0].line, 1);
+  assert!(err.error.contains("5 | comeuu 65\n  | ^^^^^^"));
+  assert!(err
+    .error
+    .contains("= note: included from tests/diagnostic.robson:1"));
+}
+
+#[test]
+fn recover_errors() {
+  let mut compiler = Compiler::new(
+    "tests/errors.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  compiler.set_recover(true);
+  let err = compiler.compile().unwrap_err();
+
+  let mut found = err
+    .diagnostics
+    .iter()
+    .map(|a| (a.line, a.code))
+    .collect::<Vec<(usize, DiagnosticCode)>>();
+  found.sort_by_key(|a| a.0);
+  assert_eq!(
+    found,
+    vec![
+      (2, DiagnosticCode::UnknownParam),
+      (5, DiagnosticCode::UndefinedLabel),
+      (7, DiagnosticCode::InvalidToken),
+      (11, DiagnosticCode::InvalidNumber),
+      (14, DiagnosticCode::InvalidOpcode),
+      (16, DiagnosticCode::MissingParam),
+    ]
+  );
+  assert!(err.error.ends_with("aborting due to 6 previous errors"));
+
+  let mut compiler = Compiler::new(
+    "tests/errors.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  compiler.set_recover(true);
+  compiler.set_max_errors(2);
+  let err = compiler.compile().unwrap_err();
+  assert_eq!(err.diagnostics.len(), 2);
+}
+
+#[test]
+fn disassemble_round_trip() {
+  for name in
+    ["types", "operations", "include", "input", "1000", "wide"]
+  {
+    let mut compiler = Compiler::new(
+      format!("tests/{name}.robson"),
+      Box::new(TestInfra::new("".to_owned())),
+    )
+    .unwrap();
+    let compiled = compiler.compile().unwrap();
+    let source = disassemble(&compiled).unwrap();
+
+    let mut compiler = Compiler::from_source(
+      format!("{name}.robson"),
+      &source,
+      Box::new(MemoryCompilerInfra::new()),
+    );
+    assert_eq!(compiler.compile().unwrap(), compiled, "{source}");
+  }
+  assert!(disassemble(&[0; 14]).is_err());
+
+  // an unknown flag wouldnt compile back to the same bytes
+  let mut buffer = [0u8; RECORD_SIZE];
+  buffer[0] = 3;
+  buffer[3] = 2;
+  assert!(disassemble(&buffer).is_err());
+}
+
+fn load_error(bytes: &[u8]) -> String {
+  Interpreter::builder()
+    .memory_cells(1)
+    .load(bytes)
+    .err()
+    .unwrap()
+    .error
+}
+
+#[test]
+fn container() {
+  let mut compiler = Compiler::new(
+    "tests/include.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let bytes = compiler.write_container(&compiled);
+
+  let container = Container::load(&bytes).unwrap();
+  assert_eq!(container.code, &compiled[..]);
+  assert_eq!(container.source, "tests/include.robson");
+  assert_eq!(container.symbols, vec![("end".to_owned(), 11)]);
+  assert_eq!(
+    container
+      .sources
+      .iter()
+      .map(|a| a.0.as_str())
+      .collect::<Vec<&str>>(),
+    vec![
+      "tests/include.robson",
+      "tests/if.robson",
+      "tests/jump.robson"
+    ]
+  );
+
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter = Interpreter::builder()
+    .memory_cells(10)
+    .load(&bytes)
+    .unwrap();
+  interpreter.run_buffer(&mut infra).unwrap();
+
+  let mut corrupted = bytes.clone();
+  corrupted[40] ^= 1;
+  assert!(load_error(&corrupted).contains("checksum"));
+
+  let mut newer = bytes.clone();
+  newer[4] = FORMAT_VERSION as u8 + 1;
+  assert!(load_error(&newer).contains("Unsupported bytecode version"));
+
+  let mut compiler = Compiler::new(
+    "tests/memory.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let bytes = compiler.write_container(&compiled);
+  assert_eq!(Container::load(&bytes).unwrap().memory, 2);
+  assert!(load_error(&bytes).contains("requires 2 memory cells"));
+}
+
+#[test]
+fn verify_buffer() {
+  for name in ["types", "operations", "include", "1000"] {
+    let mut compiler = Compiler::new(
+      format!("tests/{name}.robson"),
+      Box::new(TestInfra::new("".to_owned())),
+    )
+    .unwrap();
+    assert_eq!(verify(&compiler.compile().unwrap()), Ok(()));
+  }
+
+  let mut buffer = vec![0u8; 3 * RECORD_SIZE + 2];
+  buffer[0] = 200;
+  buffer[RECORD_SIZE] = 3;
+  buffer[RECORD_SIZE + 2..RECORD_SIZE + 4]
+    .copy_from_slice(&create_types_bytes([6, 0, 0], false));
+  buffer[2 * RECORD_SIZE] = 9;
+  buffer[2 * RECORD_SIZE + HEADER_SIZE] = 50;
+
+  let mut problems = verify(&buffer).unwrap_err();
+  problems.sort_by_key(|a| a.command);
+  assert_eq!(
+    problems
+      .iter()
+      .map(|a| (a.command, a.kind))
+      .collect::<Vec<(usize, ProblemKind)>>(),
+    vec![
+      (0, ProblemKind::InvalidOpcode(200)),
+      (1, ProblemKind::InvalidType { param: 1, bits: 6 }),
+      (2, ProblemKind::JumpOutOfRange { target: 50 }),
+      (3, ProblemKind::Truncated { offset: 48 }),
+    ]
+  );
+
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter = Interpreter::builder()
+    .memory_cells(10)
+    .build(&buffer[RECORD_SIZE..2 * RECORD_SIZE]);
+  interpreter.require_verified(true);
+  let err = interpreter.run_buffer(&mut infra).unwrap_err();
+  match err {
+    RuntimeError::Unverified { problems, .. } => assert_eq!(
+      problems[0].kind,
+      ProblemKind::InvalidType { param: 1, bits: 6 }
+    ),
+    err => panic!("unexpected {err}"),
+  }
+}
+
+#[test]
+fn invalid_type_nibble() {
+  let mut buffer = [0u8; RECORD_SIZE];
+  buffer[0] = 3;
+  buffer[2..4].copy_from_slice(&[0xF7, 0x77]);
+
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter = Interpreter::new(&buffer);
+  match interpreter.run_buffer(&mut infra).unwrap_err() {
+    RuntimeError::Unverified { problems, .. } => assert_eq!(
+      problems[0].kind,
+      ProblemKind::InvalidType { param: 1, bits: 15 }
+    ),
+    err => panic!("unexpected {err}"),
+  }
+  assert!(interpreter.next_instruction().is_none());
+  let snapshot = interpreter.snapshot();
+  assert!(Interpreter::restore(&buffer, &snapshot).is_err());
+  assert!(disassemble(&buffer).is_err());
+  assert!(crate::print_file_buffer(buffer.to_vec()).is_err());
+}
+
+#[test]
+fn debugger() {
+  let mut compiler = Compiler::new(
+    "tests/1000.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
+  interpreter.set_symbols(compiler.symbols());
+  assert_eq!(
+    interpreter.add_label_breakpoint("continue").unwrap(),
+    2
+  );
+  assert!(interpreter.add_label_breakpoint("nowhere").is_err());
+
+  // the entry point breaks before running
+  interpreter.add_breakpoint(0);
+  assert_eq!(
+    interpreter.continue_until_break(&mut infra).unwrap(),
+    Some(0)
+  );
+  assert_eq!(interpreter.executed(), 0);
+  interpreter.remove_breakpoint(0);
+  assert_eq!(
+    interpreter.continue_until_break(&mut infra).unwrap(),
+    Some(2)
+  );
+  assert_eq!(interpreter.next_instruction().unwrap().opcode, 1);
+  assert_eq!(interpreter.memory()[0], 0u32.into());
+
+  assert!(interpreter.step(&mut infra).unwrap());
+  assert_eq!(interpreter.instruction().opcode, 1);
+  assert_eq!(interpreter.stack_values(), &[1u32.into()]);
+
+  assert_eq!(
+    interpreter.continue_until_break(&mut infra).unwrap(),
+    Some(2)
+  );
+  assert_eq!(interpreter.memory()[0], 1u32.into());
+
+  interpreter.remove_breakpoint(2);
+  assert_eq!(
+    interpreter.continue_until_break(&mut infra).unwrap(),
+    None
+  );
+  assert_eq!(interpreter.memory()[0], 1000u32.into());
+  assert!(!interpreter.step(&mut infra).unwrap());
+}
+
+#[test]
+fn source_map() {
+  let mut compiler = Compiler::new(
+    "tests/include.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let location = compiler.source_map().get(6).unwrap();
+  assert_eq!(location.path, "tests/jump.robson");
+  assert_eq!(location.line, 1);
+  assert_eq!(location.origins[0].line, 3);
+
+  let bytes = compiler.write_container(&compiled);
+  let mut interpreter = Interpreter::builder()
+    .memory_cells(10)
+    .load(&bytes)
+    .unwrap();
+  assert_eq!(
+    interpreter.add_line_breakpoint("jump.robson", 3).unwrap(),
+    7
+  );
+  assert_eq!(
+    interpreter
+      .add_line_breakpoint("tests/include.robson", 7)
+      .unwrap(),
+    10
+  );
+  assert!(interpreter.add_line_breakpoint("if.robson", 40).is_err());
+}
+
+#[test]
+fn runtime_location() {
+  let mut compiler = Compiler::new(
+    "tests/located.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
+  interpreter.set_source_map(compiler.source_map().clone());
+  let err = interpreter.run_buffer(&mut infra).unwrap_err();
+  assert!(matches!(
+    err,
+    RuntimeError::TypeMismatch {
+      left: Type::Usigned,
+      right: Type::Signed,
+      ..
+    }
+  ));
+  assert_eq!(err.context().command, 1);
+  assert!(err.to_string().ends_with(
+    "--> tests/mismatch.robson:1\n  included from tests/located.robson:4"
+  ));
+}
+
+#[test]
+fn runtime_errors() {
+  let mut compiler = Compiler::new(
+    "tests/divzero.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
+
+  let err = interpreter.run_buffer(&mut infra).unwrap_err();
+  assert!(matches!(err, RuntimeError::DivisionByZero { .. }));
+  assert_eq!(err.context().command, 2);
+  assert_eq!(err.context().opcode, 1);
+  assert_eq!(err.context().stack, vec![5u32.into()]);
+
+  let mut buffer = [0u8; 2 * RECORD_SIZE];
+  buffer[0] = 7;
+  buffer[RECORD_SIZE] = 200;
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&buffer);
+  let err = interpreter.run_buffer(&mut infra).unwrap_err();
+  assert!(matches!(err, RuntimeError::StackUnderflow { .. }));
+
+  interpreter.stack.push(1u32.into()).unwrap();
+  let err = interpreter.run_buffer(&mut infra).unwrap_err();
+  assert!(matches!(err, RuntimeError::InvalidOpcode { .. }));
+  assert_eq!(err.context().command, 1);
+}
+
+fn run_overflow(arithmetic: Arithmetic) -> Vec<TypedByte> {
+  let mut compiler = Compiler::new(
+    "tests/overflow.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
+  interpreter.set_arithmetic(arithmetic);
+  interpreter.run_buffer(&mut infra).unwrap();
+  interpreter.stack_values().to_vec()
+}
+
+#[test]
+fn arithmetic_modes() {
+  let mut compiler = Compiler::new(
+    "tests/overflow.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
+  let err = interpreter.run_buffer(&mut infra).unwrap_err();
+  assert!(matches!(err, RuntimeError::Overflow { .. }));
+  assert_eq!(err.context().command, 0);
+
+  assert_eq!(
+    run_overflow(Arithmetic::Wrapping),
+    vec![1u32.into(), i32::MIN.into()]
+  );
+  assert_eq!(
+    run_overflow(Arithmetic::Saturating),
+    vec![u32::MAX.into(), i32::MAX.into()]
+  );
+  // 0 - 1 traps, wraps and saturates the same in both widths
+  let widths: [(&str, [TypedByte; 2]); 2] = [
+    ("0\ncomeu 1", [u32::MAX.into(), 0u32.into()]),
+    ("u64:0\ncomeu u64:1", [u64::MAX.into(), 0u64.into()]),
+  ];
+  for (operands, [wrapped, saturated]) in widths {
+    let mut compiler = Compiler::from_source(
+      "sub.robson".to_owned(),
+      &format!("robson\ncomeu 1\ncomeu {operands}"),
+      Box::new(TestInfra::new("".to_owned())),
+    );
+    let compiled = compiler.compile().unwrap();
+    let results = [
+      Arithmetic::Trap,
+      Arithmetic::Wrapping,
+      Arithmetic::Saturating,
+    ]
+    .map(|arithmetic| {
+      let mut interpreter = Interpreter::new(&compiled);
+      interpreter.set_arithmetic(arithmetic);
+      interpreter
+        .run_buffer(&mut infra)
+        .map(|_| interpreter.stack_values().to_vec())
+    });
+    assert!(matches!(results[0], Err(RuntimeError::Overflow { .. })));
+    assert_eq!(results[1], Ok(vec![wrapped]));
+    assert_eq!(results[2], Ok(vec![saturated]));
+  }
+}
+
+#[test]
+fn stack_bounds() {
+  let mut stack = Stack::new(2);
+  assert_eq!(stack.pop(), Err(StackError::Underflow));
+  stack.push(1u32.into()).unwrap();
+  stack.push(2u32.into()).unwrap();
+  assert_eq!(stack.push(3u32.into()), Err(StackError::Overflow));
+  assert_eq!(stack.pop(), Ok(2u32.into()));
+
+  let mut compiler = Compiler::new(
+    "tests/stackoverflow.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter = Interpreter::builder()
+    .memory_cells(10)
+    .stack_depth(100)
+    .build(&compiled);
+  let err = interpreter.run_buffer(&mut infra).unwrap_err();
+  assert!(matches!(err, RuntimeError::StackOverflow { .. }));
+  assert_eq!(interpreter.stack_values().len(), 100);
+
+  // printnumber on an empty stack
+  let mut buffer = [0u8; RECORD_SIZE];
+  buffer[0] = 8;
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&buffer);
+  let err = interpreter.run_buffer(&mut infra).unwrap_err();
+  assert!(matches!(err, RuntimeError::StackUnderflow { .. }));
+}
+
+#[test]
+fn fuel() {
+  let mut compiler = Compiler::new(
+    "tests/1000.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
+  interpreter.set_fuel(Some(1000));
+
+  let mut refuels = 0;
+  while let Err(err) = interpreter.run_buffer(&mut infra) {
+    assert!(matches!(err, RuntimeError::OutOfFuel { .. }));
+    assert!(err.is_resumable());
+    interpreter.add_fuel(1000);
+    refuels += 1;
+  }
+  assert_eq!(refuels, 4);
+  assert_eq!(interpreter.memory()[0], 1000u32.into());
+  assert_eq!(interpreter.executed(), 4002);
+
+  // jumps cost more
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
+  interpreter.set_fuel(Some(1000));
+  interpreter.set_fuel_cost(9, 10);
+  let err = interpreter.run_buffer(&mut infra).unwrap_err();
+  assert!(matches!(err, RuntimeError::OutOfFuel { .. }));
+  assert!(interpreter.executed() < 1000);
+}
+
+#[test]
+fn deadline() {
+  let mut compiler = Compiler::new(
+    "tests/forever.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
+  interpreter
+    .set_deadline(Some(Instant::now() + Duration::from_millis(20)));
+
+  let err = interpreter.run_buffer(&mut infra).unwrap_err();
+  assert!(matches!(err, RuntimeError::DeadlineExceeded { .. }));
+  assert_eq!(interpreter.executed() % DEADLINE_INTERVAL, 0);
+}
+
+#[test]
+fn suspend() {
+  let mut compiler = Compiler::new(
+    "tests/input.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
+  interpreter.suspend_on_input(true);
+
+  assert_eq!(
+    interpreter.run_for(&mut infra, 10),
+    ExecutionState::WaitingForInput
+  );
+  assert_eq!(interpreter.executed(), 0);
+  let err = interpreter.run_buffer(&mut infra).unwrap_err();
+  assert!(matches!(err, RuntimeError::WaitingForInput { .. }));
+  assert!(err.is_resumable());
+  interpreter.remove_breakpoint(0);
+  interpreter.provide_input("12".to_owned());
+  assert_eq!(
+    interpreter.run_for(&mut infra, 2),
+    ExecutionState::Running
+  );
+  assert_eq!(
+    interpreter.resume(&mut infra),
+    ExecutionState::WaitingForInput
+  );
+  interpreter.provide_input("testeteste123".to_owned());
+  assert_eq!(interpreter.resume(&mut infra), ExecutionState::Halted);
+  assert_eq!(
+    infra.stdout,
+    "12\u{c}\0\0\0t\0\0\0e\0\0\0s\0\0\0t\0\0\0"
+  );
+
+  let mut buffer = [0u8; RECORD_SIZE];
+  buffer[0] = 7;
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&buffer);
+  assert!(matches!(
+    interpreter.resume(&mut infra),
+    ExecutionState::Errored(RuntimeError::StackUnderflow { .. })
+  ));
+}
+
+fn half_run_snapshot(compiled: &[u8]) -> Vec<u8> {
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(compiled);
+  interpreter.run_for(&mut infra, 2001);
+  assert_eq!(interpreter.memory()[0], 500u32.into());
+  interpreter.snapshot()
+}
+
+fn finish_from_snapshot(compiled: &[u8], snapshot: &[u8]) {
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter = Interpreter::builder()
+    .memory_cells(10)
+    .restore(compiled, snapshot)
+    .unwrap();
+  assert_eq!(interpreter.memory()[0], 500u32.into());
+  assert_eq!(interpreter.executed(), 2001);
+  assert_eq!(interpreter.snapshot(), snapshot);
+  interpreter.run_buffer(&mut infra).unwrap();
+  assert_eq!(interpreter.memory()[0], 1000u32.into());
+}
+
+fn restore_error(
+  cells: usize,
+  buffer: &[u8],
+  snapshot: &[u8],
+) -> String {
+  Interpreter::builder()
+    .memory_cells(cells)
+    .restore(buffer, snapshot)
+    .err()
+    .unwrap()
+    .error
+}
+
+#[test]
+fn snapshot() {
+  let mut compiler = Compiler::new(
+    "tests/1000.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let snapshot = half_run_snapshot(&compiled);
+  finish_from_snapshot(&compiled, &snapshot);
+
+  assert!(
+    restore_error(5, &compiled, &snapshot).contains("memory cells")
+  );
+  assert!(restore_error(10, &compiled[RECORD_SIZE..], &snapshot)
+    .contains("another program"));
+  let mut corrupted = snapshot.clone();
+  corrupted[20] ^= 1;
+  assert!(
+    restore_error(10, &compiled, &corrupted).contains("checksum")
+  );
+}
+
+#[bench]
+fn bench_loop(b: &mut test::Bencher) {
+  let mut compiler = Compiler::new(
+    "tests/100000.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  b.iter(|| {
+    let mut interpreter =
+      Interpreter::builder().memory_cells(10).build(&compiled);
+    interpreter.run_buffer(&mut infra).unwrap();
+    assert_eq!(interpreter.memory()[0], 100000u32.into());
+  });
+}
+
+#[test]
+fn address_policy() {
+  let mut compiler = Compiler::new(
+    "tests/address.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
+  interpreter.run_buffer(&mut infra).unwrap();
+  assert_eq!(interpreter.memory()[2], 7u32.into());
+
+  let mut interpreter = Interpreter::builder()
+    .memory_cells(10)
+    .address_policy(AddressPolicy::Trap)
+    .build(&compiled);
+  let err = interpreter.run_buffer(&mut infra).unwrap_err();
+  assert!(matches!(
+    err,
+    RuntimeError::MemoryOutOfRange { address: 12, .. }
+  ));
+
+  let mut interpreter = Interpreter::builder()
+    .memory_cells(10)
+    .max_memory_cells(16)
+    .address_policy(AddressPolicy::Trap)
+    .build(&compiled);
+  interpreter.run_buffer(&mut infra).unwrap();
+  assert_eq!(interpreter.memory().len(), 13);
+  assert_eq!(interpreter.memory()[12], 7u32.into());
+}
+
+#[test]
+fn call_ret() {
+  let mut compiler = Compiler::new(
+    "tests/call.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter =
+    Interpreter::builder().memory_cells(10).build(&compiled);
+  interpreter.run_buffer(&mut infra).unwrap();
+  assert_eq!(interpreter.memory()[0], 12u32.into());
+  assert!(interpreter.stack_values().is_empty());
+  assert!(disassemble(&compiled).unwrap().contains("lambeu :l"));
+
+  let mut compiler = Compiler::new(
+    "tests/recursion.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut interpreter = Interpreter::builder()
+    .memory_cells(10)
+    .call_depth(4)
+    .build(&compiled);
+  let err = interpreter.run_buffer(&mut infra).unwrap_err();
+  assert!(matches!(err, RuntimeError::CallStackOverflow { .. }));
+  assert_eq!(interpreter.executed(), 5);
+
+  let mut buffer = [0u8; RECORD_SIZE];
+  buffer[0] = 18;
+  let mut interpreter = Interpreter::new(&buffer);
+  let err = interpreter.run_buffer(&mut infra).unwrap_err();
+  assert!(matches!(err, RuntimeError::CallStackUnderflow { .. }));
+}
+
+#[test]
+fn bitwise() {
+  let mut compiler = Compiler::new(
+    "tests/bitwise.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter = Interpreter::new(&compiled);
+  interpreter.run_buffer(&mut infra).unwrap();
+  assert_eq!(
+    interpreter.stack_values(),
+    &[
+      8u32.into(),
+      14u32.into(),
+      6u32.into(),
+      u32::MAX.into(),
+      16u32.into(),
+      16u32.into(),
+      (-4i32).into(),
+      0x3FFFFFFCi32.into(),
+    ]
+  );
+
+  assert_eq!(u32_shl(1u32.to_le_bytes(), [32, 0, 0, 0], Trap), None);
+  assert_eq!(
+    u32_shl(1u32.to_le_bytes(), [32, 0, 0, 0], Saturating),
+    Some(0)
+  );
+  assert_eq!(
+    i32_shr((-8i32).to_le_bytes(), [40, 0, 0, 0], Saturating),
+    Some(-1)
+  );
+  assert!(OPERATIONS[5][Type::Floating as usize].is_none());
+
+  let mut compiler = Compiler::new(
+    "tests/badkind.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut interpreter = Interpreter::new(&compiled);
+  let err = interpreter.run_buffer(&mut infra).unwrap_err();
+  assert!(matches!(
+    err,
+    RuntimeError::InvalidOperation { kind: 12, .. }
+  ));
+}
+
+#[test]
+fn comparisons() {
+  let mut compiler = Compiler::new(
+    "tests/compare.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter = Interpreter::new(&compiled);
+  interpreter.run_buffer(&mut infra).unwrap();
+  assert_eq!(interpreter.stack_values(), &[1u32.into(), 0u32.into()]);
+
+  assert_eq!(
+    compare(1u32.into(), 1i32.into()),
+    Err((Type::Usigned, Type::Signed))
+  );
+  assert_eq!(compare(f32::NAN.into(), 1f32.into()), Ok(None));
+  assert!(COMPARISONS[1](None));
+  assert!(!COMPARISONS[5](None));
+}
+
+#[test]
+fn wide_types() {
+  let mut compiler = Compiler::new(
+    "tests/wide.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  // only the 8 params of a 64-bit type take 8 bytes
+  assert_eq!(compiled.len(), 6 * RECORD_SIZE + 8 * 4);
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter = Interpreter::new(&compiled);
+  interpreter.run_buffer(&mut infra).unwrap();
+  assert_eq!(infra.stdout, "12345678901");
+  assert_eq!(
+    interpreter.stack_values(),
+    &[
+      8_000_000_000u64.into(),
+      (-6_000_000_000i64).into(),
+      u64::MAX.into(),
+      (1f64 / 3.0).into(),
+    ]
+  );
+
+  let mut byte: TypedByte = u64::MAX.into();
+  assert!(!OPERATIONS[0][Type::Usigned64 as usize].unwrap()(
+    &mut byte,
+    1u64.into(),
+    Trap
+  ));
+  assert!(OPERATIONS[0][Type::Usigned64 as usize].unwrap()(
+    &mut byte,
+    1u64.into(),
+    Saturating
+  ));
+  assert_eq!(byte, u64::MAX.into());
+
+  let mut byte: TypedByte = (-1i32).into();
+  byte.convert(Type::Usigned);
+  assert_eq!(byte, u32::MAX.into());
+  byte.convert(Type::Signed64);
+  assert_eq!(byte, (u32::MAX as i64).into());
+  let mut byte: TypedByte = 1e20f64.into();
+  byte.convert(Type::Usigned);
+  assert_eq!(byte, u32::MAX.into());
+  assert_eq!(TypedByte::from(-2i64).to_string(), "i64:-2");
+}
+
+#[test]
+fn parser() {
+  let tokens = tokenize(0, "comeu\t 'a b;c'  robson ; comment");
+  assert_eq!(
+    tokens
+      .iter()
+      .map(|a| a.text.as_str())
+      .collect::<Vec<&str>>(),
+    ["comeu", "'a b;c'", "robson"]
+  );
+  // a comment needs no space before it
+  assert_eq!(
+    tokenize(0, "comeu 5;comment")
+      .iter()
+      .map(|a| a.text.as_str())
+      .collect::<Vec<&str>>(),
+    ["comeu", "5"]
+  );
+  assert_eq!(
+    parse_line(0, "robson robson;x").unwrap().statement,
+    Statement::Opcode(2)
+  );
+  assert_eq!(tokens[1].span.columns("comeu\t 'a b;c'"), 8..15);
+  assert_eq!(
+    parse_line(0, "[robsons  a.robson] ;x").unwrap().statement,
+    Statement::Macro("a.robson".to_owned())
+  );
+  assert_eq!(
+    parse_line(0, "robson\trobson").unwrap().statement,
+    Statement::Opcode(2)
+  );
+  assert_eq!(
+    parse_line(0, "robson robsonn")
+      .unwrap_err()
+      .span
+      .columns("robson robsonn"),
+    8..15
+  );
+
+  let files = MemoryCompilerInfra::new()
+    .file(
+      "print.robson",
+      "1$ROBSON\nrobson robson robson\n1$ROBSON\nrobson robson robson \
+       robson robson robson robson robson",
+    )
+    .file(
+      "split.robson",
+      "1$ROBSON\ns,cc 1$ROBSON\nrobson robson robson\n1?ROBSON\nrobson \
+       robson robson robson robson robson robson",
+    );
+  let mut compiler = Compiler::from_source(
+    "main.robson".to_owned(),
+    "[robsons print.robson]\ncomeu 7;seven\nrobson robson \
+     robson\t; push\n  comeu  5\nrobson robson robson robson robson \
+     robson robson robson;print\n[robsons split.robson]\n9,8",
+    Box::new(files),
+  );
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  Interpreter::new(&compiled).run_buffer(&mut infra).unwrap();
+  assert_eq!(infra.stdout, "759\0\0\0");
+}
+
+#[test]
+fn backpatch() {
+  let files = MemoryCompilerInfra::new()
+    .file(
+      "goto.robson",
+      "1$ROBSON\nrobson robson robson robson robson robson robson \
+       robson robson\n1$ROBSON",
+    )
+    .file("outer.robson", "1$ROBSON\n[robsons goto.robson]\n1$ROBSON")
+    .file("push.robson", "1$ROBSON\nrobson robson robson\n1$ROBSON");
+  let source = "robson robson robson\ncomeu 7\n[robsons \
+                goto.robson]\nlambeu :print\nskip:\nrobson robson \
+                robson\ncomeu 9\nprint:\nrobson robson robson robson \
+                robson robson robson robson\n[robsons \
+                outer.robson]\nlambeu :end\n[robsons \
+                goto.robson]\nlambeu :skip\nend:";
+  let mut compiler = Compiler::from_source(
+    "main.robson".to_owned(),
+    source,
+    Box::new(files.clone()),
+  );
+  let compiled = compiler.compile().unwrap();
+  assert_eq!(compiled.len(), 6 * RECORD_SIZE);
+  assert_eq!(
+    compiler.symbols(),
+    [
+      ("skip".to_owned(), 2),
+      ("print".to_owned(), 3),
+      ("end".to_owned(), 6)
+    ]
+  );
+  let mut infra = TestInfra::new("".to_owned());
+  Interpreter::new(&compiled).run_buffer(&mut infra).unwrap();
+  assert_eq!(infra.stdout, "7");
+
+  let mut compiler = Compiler::from_source(
+    "main.robson".to_owned(),
+    "[robsons outer.robson]\nlambeu :nowhere",
+    Box::new(files.clone()),
+  );
+  let err = compiler.compile().unwrap_err();
+  assert_eq!(err.diagnostics[0].code, DiagnosticCode::UndefinedLabel);
+  assert_eq!(err.diagnostics[0].line, 1);
+
+  // expressions over labels defined later
+  let print =
+    "robson robson robson robson robson robson robson robson";
+  let mut compiler = Compiler::from_source(
+    "main.robson".to_owned(),
+    &format!(
+      "[robsons push.robson]\ncomeu :end + 1\n{print}\n[robsons \
+       outer.robson]\nlambeu :end + 0\nrobson robson \
+       robson\ncomeu 7\n{print}\nend:"
+    ),
+    Box::new(files),
+  );
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  Interpreter::new(&compiled).run_buffer(&mut infra).unwrap();
+  assert_eq!(infra.stdout, "6");
+
+  // forwarded expressions are written back as text
+  let label = |_: &str| Some(3);
+  let numbers: [TypedByte; 4] = [
+    i32::MIN.into(),
+    (-1.5f32).into(),
+    i64::MIN.into(),
+    (-0f64).into(),
+  ];
+  for number in numbers {
+    let expr = Expr::Binary(
+      Operator::Add,
+      Box::new(Expr::Number(number)),
+      Box::new(Expr::Label("a".to_owned())),
+    );
+    let text = expr.to_string();
+    let parsed = Expr::parse(&text, &HashMap::new()).unwrap();
+    assert!(expr.eval(&label).is_ok());
+    assert_eq!(parsed.eval(&label), expr.eval(&label), "{text}");
+  }
+}
+
+#[test]
+fn constants() {
+  let mut compiler = Compiler::new(
+    "tests/constants.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  Interpreter::new(&compiled).run_buffer(&mut infra).unwrap();
+  assert_eq!(infra.stdout, "600000000010-650.5131");
+
+  let files = MemoryCompilerInfra::new().file(
+    "print.robson",
+    "1$ROBSON\nrobson robson robson\n1$ROBSON\nrobson robson robson \
+     robson robson robson robson robson",
+  );
+  let mut compiler = Compiler::from_source(
+    "main.robson".to_owned(),
+    "const X = 6\n[robsons print.robson]\ncomeu X * 7",
+    Box::new(files),
+  );
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  Interpreter::new(&compiled).run_buffer(&mut infra).unwrap();
+  assert_eq!(infra.stdout, "42");
+
+  let mut compiler = Compiler::from_source(
+    "main.robson".to_owned(),
+    &[
+      "const A = 1",
+      "const A = 2",
+      "const = 3",
+      "robson robson robson",
+      "comeu 1 / 0",
+      "robson robson robson",
+      "comeu NOPE + 1",
+      "robson robson robson",
+      "comeu 4000000000 * 2",
+      "robson robson robson robson robson robson robson robson \
+       robson robson",
+      "chupou f1.5",
+      "robson robson robson",
+      "comeu (1 +",
+      "robson robson robson",
+      "comeu 2 * 1x",
+    ]
+    .join("\n"),
+    Box::new(MemoryCompilerInfra::new()),
+  );
+  compiler.set_recover(true);
+  let err = compiler.compile().unwrap_err();
+  assert_eq!(
+    err
+      .diagnostics
+      .iter()
+      .map(|a| (a.line, a.code))
+      .collect::<Vec<(usize, DiagnosticCode)>>(),
+    [
+      (2, DiagnosticCode::DuplicatedConstant),
+      (3, DiagnosticCode::MalformedConstant),
+      (5, DiagnosticCode::InvalidExpression),
+      (7, DiagnosticCode::UndefinedConstant),
+      (9, DiagnosticCode::InvalidExpression),
+      (11, DiagnosticCode::InvalidNumber),
+      (13, DiagnosticCode::InvalidExpression),
+      (15, DiagnosticCode::InvalidNumber),
+    ]
+  );
+}
+
+#[test]
+fn strings() {
+  let mut compiler = Compiler::new(
+    "tests/string.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter = Interpreter::new(&compiled);
+  interpreter.run_buffer(&mut infra).unwrap();
+  let printed = ['h', 'i', ';', '\n', '"', 'é', '\t']
+    .map(|a| {
+      let mut bytes = [0; 4];
+      a.encode_utf8(&mut bytes);
+      String::from_utf8_lossy(&bytes).into_owned()
+    })
+    .concat();
+  assert_eq!(infra.stdout, format!("{printed}3933"));
+  assert_eq!(interpreter.memory()[9], 0u32.into());
+
+  let mut compiler = Compiler::from_source(
+    "main.robson".to_owned(),
+    "string \"a\\q\"\nstring 1\nrobson robson robson\ncomeu 'ab'",
+    Box::new(MemoryCompilerInfra::new()),
+  );
+  compiler.set_recover(true);
+  let err = compiler.compile().unwrap_err();
+  assert_eq!(
+    err
+      .diagnostics
+      .iter()
+      .map(|a| (a.line, a.code))
+      .collect::<Vec<(usize, DiagnosticCode)>>(),
+    [
+      (1, DiagnosticCode::MalformedString),
+      (2, DiagnosticCode::MalformedString),
+      (4, DiagnosticCode::InvalidNumber),
+    ]
+  );
+}
+
+#[test]
+fn variables() {
+  let mut compiler = Compiler::new(
+    "tests/variables.robson".to_owned(),
+    Box::new(TestInfra::new("".to_owned())),
+  )
+  .unwrap();
+  let compiled = compiler.compile().unwrap();
+  let mut infra = TestInfra::new("".to_owned());
+  Interpreter::new(&compiled).run_buffer(&mut infra).unwrap();
+  assert_eq!(infra.stdout, "-7141a\0\0\0b\0\0\0");
+
+  let map = compiler.memory_map();
+  assert_eq!(
+    map
+      .variables
+      .iter()
+      .map(|a| (a.name.as_str(), a.address, a.size))
+      .collect::<Vec<(&str, usize, usize)>>(),
+    [
+      ("counter", 0, 1),
+      ("ratio", 1, 1),
+      ("name", 2, 4),
+      ("total", 6, 1)
+    ]
+  );
+  assert_eq!(map.get(4).unwrap().name, "name");
+  assert_eq!(map.end(), 7);
+  assert_eq!(
+    map.to_string().lines().nth(4).unwrap(),
+    "6        1     i32   total (tests/counter.robson:1)"
+  );
+
+  let mut compiler = Compiler::from_source(
+    "main.robson".to_owned(),
+    &[
+      "var a: u32",
+      "var a: u32",
+      "var b: u33",
+      "var c: [2] = \"ab\"",
+      "var d: [0]",
+      "robson robson robson",
+      "fudeu &e",
+    ]
+    .join("\n"),
+    Box::new(MemoryCompilerInfra::new()),
+  );
+  compiler.set_recover(true);
+  compiler.set_data_start(100);
+  let err = compiler.compile().unwrap_err();
+  assert_eq!(
+    err
+      .diagnostics
+      .iter()
+      .map(|a| (a.line, a.code))
+      .collect::<Vec<(usize, DiagnosticCode)>>(),
+    [
+      (2, DiagnosticCode::DuplicatedVariable),
+      (3, DiagnosticCode::MalformedVariable),
+      (4, DiagnosticCode::MalformedVariable),
+      (5, DiagnosticCode::MalformedVariable),
+      (7, DiagnosticCode::UndefinedVariable),
+    ]
+  );
+  assert_eq!(compiler.memory_map().variables[0].address, 100);
+
+  // set once before the jump over it, after the cell written by hand
+  let print =
+    "robson robson robson robson robson robson robson robson";
+  let files = MemoryCompilerInfra::new().file(
+    "show.robson",
+    &format!("1$ROBSON\nrobson robson robson\n1$ROBSON\n{print}"),
+  );
+  let mut compiler = Compiler::from_source(
+    "main.robson".to_owned(),
+    &format!(
+      "robson robson robson robson robson robson robson robson \
+       robson\nlambeu :end\nvar x: u32 = 7\nvar y: u32 = &x * \
+       2\nend:\nrobson robson robson\ncomeu 1\nrobson robson robson \
+       robson robson robson robson robson robson robson\ncomeu \
+       3\nrobson robson robson\nfudeu &x\n{print}\nrobson robson \
+       robson\ncomeu &x * 2\n{print}\n[robsons show.robson]\nfudeu \
+       &y"
+    ),
+    Box::new(files),
+  );
+  let compiled = compiler.compile().unwrap();
+  assert_eq!(compiler.memory_map().variables[0].address, 4);
+  assert_eq!(compiler.symbols(), [("end".to_owned(), 2)]);
+  let mut infra = TestInfra::new("".to_owned());
+  let mut interpreter = Interpreter::new(&compiled);
+  interpreter.run_buffer(&mut infra).unwrap();
+  assert_eq!(infra.stdout, "788");
+  assert_eq!(interpreter.memory()[3], 1u32.into());
+
+  // after every cell a string input can write
+  let mut compiler = Compiler::from_source(
+    "main.robson".to_owned(),
+    &format!(
+      "robson robson robson robson robson robson\ncomeu 0\ncomeu \
+       0\ncomeu 10\nvar x: u32 = 7\nrobson robson robson\nfudeu \
+       &x\n{print}"
+    ),
+    Box::new(MemoryCompilerInfra::new()),
+  );
+  let compiled = compiler.compile().unwrap();
+  assert_eq!(compiler.memory_map().variables[0].address, 11);
+  assert!(verify(&compiled).is_ok());
+  let mut infra = TestInfra::new("hello world".to_owned());
+  let mut interpreter = Interpreter::new(&compiled);
+  interpreter.run_buffer(&mut infra).unwrap();
+  assert_eq!(infra.stdout, "7");
+  assert_eq!(interpreter.memory()[10], 0u32.into());
+}
@@ -1,64 +1,439 @@
 use std::collections::HashMap;
 
-use crate::{data_struct::IError, macros::ierror};
+use crate::{
+  data_struct::{Arithmetic, IError},
+  macros::ierror,
+};
+
+pub const fn u32_add(
+  a: [u8; 4],
+  b: [u8; 4],
+  mode: Arithmetic,
+) -> Option<u32> {
+  let (a, b) = (u32_from_bytes(a), u32_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_add(b),
+    Arithmetic::Wrapping => Some(a.wrapping_add(b)),
+    Arithmetic::Saturating => Some(a.saturating_add(b)),
+  }
+}
 
-pub const fn u32_add(a: [u8; 4], b: [u8; 4]) -> u32 {
-  u32_from_bytes(a) + u32_from_bytes(b)
+pub const fn i32_add(
+  a: [u8; 4],
+  b: [u8; 4],
+  mode: Arithmetic,
+) -> Option<i32> {
+  let (a, b) = (i32_from_bytes(a), i32_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_add(b),
+    Arithmetic::Wrapping => Some(a.wrapping_add(b)),
+    Arithmetic::Saturating => Some(a.saturating_add(b)),
+  }
 }
 
 pub fn f32_add(a: [u8; 4], b: [u8; 4]) -> f32 {
   f32_from_bytes(a) + f32_from_bytes(b)
 }
 
-pub const fn i32_add(a: [u8; 4], b: [u8; 4]) -> i32 {
-  i32_from_bytes(a) + i32_from_bytes(b)
+/// Unsigned results stop at zero unless wrapping is asked, as they
+/// always did in robson
+pub const fn u32_sub(
+  a: [u8; 4],
+  b: [u8; 4],
+  mode: Arithmetic,
+) -> Option<u32> {
+  let (a, b) = (u32_from_bytes(a), u32_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_sub(b),
+    Arithmetic::Wrapping => Some(a.wrapping_sub(b)),
+    Arithmetic::Saturating => Some(a.saturating_sub(b)),
+  }
 }
 
-pub const fn u32_sub(a: [u8; 4], b: [u8; 4]) -> u32 {
-  u32_from_bytes(a) - u32_from_bytes(b)
+pub const fn i32_sub(
+  a: [u8; 4],
+  b: [u8; 4],
+  mode: Arithmetic,
+) -> Option<i32> {
+  let (a, b) = (i32_from_bytes(a), i32_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_sub(b),
+    Arithmetic::Wrapping => Some(a.wrapping_sub(b)),
+    Arithmetic::Saturating => Some(a.saturating_sub(b)),
+  }
 }
 
 pub fn f32_sub(a: [u8; 4], b: [u8; 4]) -> f32 {
   f32_from_bytes(a) - f32_from_bytes(b)
 }
 
-pub const fn i32_sub(a: [u8; 4], b: [u8; 4]) -> i32 {
-  i32_from_bytes(a) - i32_from_bytes(b)
+pub const fn u32_mul(
+  a: [u8; 4],
+  b: [u8; 4],
+  mode: Arithmetic,
+) -> Option<u32> {
+  let (a, b) = (u32_from_bytes(a), u32_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_mul(b),
+    Arithmetic::Wrapping => Some(a.wrapping_mul(b)),
+    Arithmetic::Saturating => Some(a.saturating_mul(b)),
+  }
 }
-pub const fn u32_mul(a: [u8; 4], b: [u8; 4]) -> u32 {
-  u32_from_bytes(a) * u32_from_bytes(b)
+
+pub const fn i32_mul(
+  a: [u8; 4],
+  b: [u8; 4],
+  mode: Arithmetic,
+) -> Option<i32> {
+  let (a, b) = (i32_from_bytes(a), i32_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_mul(b),
+    Arithmetic::Wrapping => Some(a.wrapping_mul(b)),
+    Arithmetic::Saturating => Some(a.saturating_mul(b)),
+  }
 }
 
 pub fn f32_mul(a: [u8; 4], b: [u8; 4]) -> f32 {
   f32_from_bytes(a) * f32_from_bytes(b)
 }
 
-pub const fn i32_mul(a: [u8; 4], b: [u8; 4]) -> i32 {
-  i32_from_bytes(a) * i32_from_bytes(b)
+/// Division by zero gives `None` in every mode
+pub const fn u32_div(
+  a: [u8; 4],
+  b: [u8; 4],
+  _mode: Arithmetic,
+) -> Option<u32> {
+  u32_from_bytes(a).checked_div(u32_from_bytes(b))
 }
 
-pub const fn u32_div(a: [u8; 4], b: [u8; 4]) -> u32 {
-  u32_from_bytes(a) / u32_from_bytes(b)
+pub const fn i32_div(
+  a: [u8; 4],
+  b: [u8; 4],
+  mode: Arithmetic,
+) -> Option<i32> {
+  let (a, b) = (i32_from_bytes(a), i32_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_div(b),
+    Arithmetic::Wrapping => {
+      if b == 0 {
+        None
+      } else {
+        Some(a.wrapping_div(b))
+      }
+    }
+    Arithmetic::Saturating => {
+      if b == 0 {
+        None
+      } else if a == i32::MIN && b == -1 {
+        Some(i32::MAX)
+      } else {
+        Some(a / b)
+      }
+    }
+  }
 }
 
 pub fn f32_div(a: [u8; 4], b: [u8; 4]) -> f32 {
   f32_from_bytes(a) / f32_from_bytes(b)
 }
 
-pub const fn i32_div(a: [u8; 4], b: [u8; 4]) -> i32 {
-  i32_from_bytes(a) / i32_from_bytes(b)
+pub const fn u32_mod(
+  a: [u8; 4],
+  b: [u8; 4],
+  _mode: Arithmetic,
+) -> Option<u32> {
+  u32_from_bytes(a).checked_rem(u32_from_bytes(b))
 }
 
-pub const fn u32_mod(a: [u8; 4], b: [u8; 4]) -> u32 {
-  u32_from_bytes(a) % u32_from_bytes(b)
+pub const fn i32_mod(
+  a: [u8; 4],
+  b: [u8; 4],
+  mode: Arithmetic,
+) -> Option<i32> {
+  let (a, b) = (i32_from_bytes(a), i32_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_rem(b),
+    Arithmetic::Wrapping => {
+      if b == 0 {
+        None
+      } else {
+        Some(a.wrapping_rem(b))
+      }
+    }
+    Arithmetic::Saturating => {
+      if b == 0 {
+        None
+      } else if a == i32::MIN && b == -1 {
+        Some(0)
+      } else {
+        Some(a % b)
+      }
+    }
+  }
 }
 
 pub fn f32_mod(a: [u8; 4], b: [u8; 4]) -> f32 {
   f32_from_bytes(a) % f32_from_bytes(b)
 }
 
-pub const fn i32_mod(a: [u8; 4], b: [u8; 4]) -> i32 {
-  i32_from_bytes(a) % i32_from_bytes(b)
+/// Shifting by 32 or more overflows, wrapping masks the amount and
+/// saturating shifts every bit out
+pub const fn u32_shl(
+  a: [u8; 4],
+  b: [u8; 4],
+  mode: Arithmetic,
+) -> Option<u32> {
+  let (a, b) = (u32_from_bytes(a), u32_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_shl(b),
+    Arithmetic::Wrapping => Some(a.wrapping_shl(b)),
+    Arithmetic::Saturating => Some(if b < 32 { a << b } else { 0 }),
+  }
+}
+
+pub const fn u32_shr(
+  a: [u8; 4],
+  b: [u8; 4],
+  mode: Arithmetic,
+) -> Option<u32> {
+  let (a, b) = (u32_from_bytes(a), u32_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_shr(b),
+    Arithmetic::Wrapping => Some(a.wrapping_shr(b)),
+    Arithmetic::Saturating => Some(if b < 32 { a >> b } else { 0 }),
+  }
+}
+
+/// Keeps the sign, saturating fills every bit with it
+pub const fn i32_shr(
+  a: [u8; 4],
+  b: [u8; 4],
+  mode: Arithmetic,
+) -> Option<i32> {
+  let (a, b) = (i32_from_bytes(a), u32_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_shr(b),
+    Arithmetic::Wrapping => Some(a.wrapping_shr(b)),
+    Arithmetic::Saturating => {
+      Some(if b < 32 { a >> b } else { a >> 31 })
+    }
+  }
+}
+
+/// The 64-bit versions behave like the 32-bit ones above
+pub const fn u64_add(
+  a: [u8; 8],
+  b: [u8; 8],
+  mode: Arithmetic,
+) -> Option<u64> {
+  let (a, b) = (u64_from_bytes(a), u64_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_add(b),
+    Arithmetic::Wrapping => Some(a.wrapping_add(b)),
+    Arithmetic::Saturating => Some(a.saturating_add(b)),
+  }
+}
+
+pub const fn i64_add(
+  a: [u8; 8],
+  b: [u8; 8],
+  mode: Arithmetic,
+) -> Option<i64> {
+  let (a, b) = (i64_from_bytes(a), i64_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_add(b),
+    Arithmetic::Wrapping => Some(a.wrapping_add(b)),
+    Arithmetic::Saturating => Some(a.saturating_add(b)),
+  }
+}
+
+pub fn f64_add(a: [u8; 8], b: [u8; 8]) -> f64 {
+  f64_from_bytes(a) + f64_from_bytes(b)
+}
+
+pub const fn u64_sub(
+  a: [u8; 8],
+  b: [u8; 8],
+  mode: Arithmetic,
+) -> Option<u64> {
+  let (a, b) = (u64_from_bytes(a), u64_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_sub(b),
+    Arithmetic::Wrapping => Some(a.wrapping_sub(b)),
+    Arithmetic::Saturating => Some(a.saturating_sub(b)),
+  }
+}
+
+pub const fn i64_sub(
+  a: [u8; 8],
+  b: [u8; 8],
+  mode: Arithmetic,
+) -> Option<i64> {
+  let (a, b) = (i64_from_bytes(a), i64_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_sub(b),
+    Arithmetic::Wrapping => Some(a.wrapping_sub(b)),
+    Arithmetic::Saturating => Some(a.saturating_sub(b)),
+  }
+}
+
+pub fn f64_sub(a: [u8; 8], b: [u8; 8]) -> f64 {
+  f64_from_bytes(a) - f64_from_bytes(b)
+}
+
+pub const fn u64_mul(
+  a: [u8; 8],
+  b: [u8; 8],
+  mode: Arithmetic,
+) -> Option<u64> {
+  let (a, b) = (u64_from_bytes(a), u64_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_mul(b),
+    Arithmetic::Wrapping => Some(a.wrapping_mul(b)),
+    Arithmetic::Saturating => Some(a.saturating_mul(b)),
+  }
+}
+
+pub const fn i64_mul(
+  a: [u8; 8],
+  b: [u8; 8],
+  mode: Arithmetic,
+) -> Option<i64> {
+  let (a, b) = (i64_from_bytes(a), i64_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_mul(b),
+    Arithmetic::Wrapping => Some(a.wrapping_mul(b)),
+    Arithmetic::Saturating => Some(a.saturating_mul(b)),
+  }
+}
+
+pub fn f64_mul(a: [u8; 8], b: [u8; 8]) -> f64 {
+  f64_from_bytes(a) * f64_from_bytes(b)
+}
+
+pub const fn u64_div(
+  a: [u8; 8],
+  b: [u8; 8],
+  _mode: Arithmetic,
+) -> Option<u64> {
+  u64_from_bytes(a).checked_div(u64_from_bytes(b))
+}
+
+pub const fn i64_div(
+  a: [u8; 8],
+  b: [u8; 8],
+  mode: Arithmetic,
+) -> Option<i64> {
+  let (a, b) = (i64_from_bytes(a), i64_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_div(b),
+    Arithmetic::Wrapping => {
+      if b == 0 {
+        None
+      } else {
+        Some(a.wrapping_div(b))
+      }
+    }
+    Arithmetic::Saturating => {
+      if b == 0 {
+        None
+      } else if a == i64::MIN && b == -1 {
+        Some(i64::MAX)
+      } else {
+        Some(a / b)
+      }
+    }
+  }
+}
+
+pub fn f64_div(a: [u8; 8], b: [u8; 8]) -> f64 {
+  f64_from_bytes(a) / f64_from_bytes(b)
+}
+
+pub const fn u64_mod(
+  a: [u8; 8],
+  b: [u8; 8],
+  _mode: Arithmetic,
+) -> Option<u64> {
+  u64_from_bytes(a).checked_rem(u64_from_bytes(b))
+}
+
+pub const fn i64_mod(
+  a: [u8; 8],
+  b: [u8; 8],
+  mode: Arithmetic,
+) -> Option<i64> {
+  let (a, b) = (i64_from_bytes(a), i64_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => a.checked_rem(b),
+    Arithmetic::Wrapping => {
+      if b == 0 {
+        None
+      } else {
+        Some(a.wrapping_rem(b))
+      }
+    }
+    Arithmetic::Saturating => {
+      if b == 0 {
+        None
+      } else if a == i64::MIN && b == -1 {
+        Some(0)
+      } else {
+        Some(a % b)
+      }
+    }
+  }
+}
+
+pub fn f64_mod(a: [u8; 8], b: [u8; 8]) -> f64 {
+  f64_from_bytes(a) % f64_from_bytes(b)
+}
+
+pub const fn u64_shl(
+  a: [u8; 8],
+  b: [u8; 8],
+  mode: Arithmetic,
+) -> Option<u64> {
+  let (a, b) = (u64_from_bytes(a), u64_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => {
+      a.checked_shl(if b < 64 { b as u32 } else { 64 })
+    }
+    Arithmetic::Wrapping => Some(a.wrapping_shl(b as u32)),
+    Arithmetic::Saturating => Some(if b < 64 { a << b } else { 0 }),
+  }
+}
+
+pub const fn u64_shr(
+  a: [u8; 8],
+  b: [u8; 8],
+  mode: Arithmetic,
+) -> Option<u64> {
+  let (a, b) = (u64_from_bytes(a), u64_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => {
+      a.checked_shr(if b < 64 { b as u32 } else { 64 })
+    }
+    Arithmetic::Wrapping => Some(a.wrapping_shr(b as u32)),
+    Arithmetic::Saturating => Some(if b < 64 { a >> b } else { 0 }),
+  }
+}
+
+pub const fn i64_shr(
+  a: [u8; 8],
+  b: [u8; 8],
+  mode: Arithmetic,
+) -> Option<i64> {
+  let (a, b) = (i64_from_bytes(a), u64_from_bytes(b));
+  match mode {
+    Arithmetic::Trap => {
+      a.checked_shr(if b < 64 { b as u32 } else { 64 })
+    }
+    Arithmetic::Wrapping => Some(a.wrapping_shr(b as u32)),
+    Arithmetic::Saturating => {
+      Some(if b < 64 { a >> b } else { a >> 63 })
+    }
+  }
 }
 
 pub fn approx_equal(a: f32, b: f32, decimal_places: u8) -> bool {
@@ -68,6 +443,11 @@ pub fn approx_equal(a: f32, b: f32, decimal_places: u8) -> bool {
   a == b
 }
 
+pub fn approx_equal_f64(a: f64, b: f64, decimal_places: u8) -> bool {
+  let factor = 10.0f64.powi(decimal_places as i32);
+  (a * factor).trunc() == (b * factor).trunc()
+}
+
 pub const fn u32_to_bytes(number: u32) -> [u8; 4] {
   unsafe { std::mem::transmute(number) }
 }
@@ -75,6 +455,14 @@ pub const fn u32_from_bytes(bytes: [u8; 4]) -> u32 {
   unsafe { std::mem::transmute(bytes) }
 }
 
+/// The UTF-8 bytes of `char` as a cell, how strings are kept in
+/// memory
+pub fn char_to_u32(char: char) -> u32 {
+  let mut bytes = [0; 4];
+  char.encode_utf8(&mut bytes);
+  u32_from_bytes(bytes)
+}
+
 pub const fn i32_to_bytes(number: i32) -> [u8; 4] {
   unsafe { std::mem::transmute(number) }
 }
@@ -89,6 +477,32 @@ pub const fn f32_from_bytes(bytes: [u8; 4]) -> f32 {
   unsafe { std::mem::transmute(bytes) }
 }
 
+pub const fn u64_to_bytes(number: u64) -> [u8; 8] {
+  number.to_ne_bytes()
+}
+pub const fn u64_from_bytes(bytes: [u8; 8]) -> u64 {
+  u64::from_ne_bytes(bytes)
+}
+
+pub const fn i64_to_bytes(number: i64) -> [u8; 8] {
+  number.to_ne_bytes()
+}
+pub const fn i64_from_bytes(bytes: [u8; 8]) -> i64 {
+  i64::from_ne_bytes(bytes)
+}
+
+pub const fn f64_to_bytes(number: f64) -> [u8; 8] {
+  number.to_ne_bytes()
+}
+pub const fn f64_from_bytes(bytes: [u8; 8]) -> f64 {
+  f64::from_ne_bytes(bytes)
+}
+
+/// Pads a 32-bit value to the 8 bytes of a param
+pub const fn widen(bytes: [u8; 4]) -> [u8; 8] {
+  [bytes[0], bytes[1], bytes[2], bytes[3], 0, 0, 0, 0]
+}
+
 pub const fn create_kind_byte(
   type1: u8,
   type2: u8,
@@ -111,6 +525,27 @@ pub const fn convert_kind_byte(a: u8) -> [usize; 4] {
   ]
 }
 
+/// The two bytes after the kind byte of a record, a nibble for each
+/// param type and one for the flags, only the convert flag of param 3
+/// is defined
+pub const fn create_types_bytes(
+  types: [u8; 3],
+  param3_convert: bool,
+) -> [u8; 2] {
+  [
+    types[0] << 4 | types[1],
+    types[2] << 4 | param3_convert as u8,
+  ]
+}
+pub const fn convert_types_bytes(bytes: [u8; 2]) -> [usize; 4] {
+  [
+    (bytes[0] >> 4) as usize,
+    (bytes[0] & 15) as usize,
+    (bytes[1] >> 4) as usize,
+    (bytes[1] & 15) as usize,
+  ]
+}
+
 pub const fn create_two_bits(bits: [bool; 2]) -> u8 {
   let mut byte = 0;
   byte += bits[0] as u8;
@@ -178,10 +613,6 @@ pub fn convert_macro_robson(
           let chars = value.chars().collect::<Vec<char>>();
           value = String::new();
           for i in chars {
-            let mut bytes: [u8; 4] = [0, 0, 0, 0];
-
-            i.encode_utf8(&mut bytes);
-
             let prefix = match char {
               'c' => "comeu",
               'f' => "fudeu",
@@ -192,7 +623,7 @@ pub fn convert_macro_robson(
               }
             };
 
-            let number = u32_from_bytes(bytes);
+            let number = char_to_u32(i);
             value.push_str(&format!("{prefix} {number}\n"));
           }
         }
@@ -223,3 +654,20 @@ pub fn convert_macro_robson(
 
   Ok((value, has_next, is_expr))
 }
+
+/// CRC-32 (IEEE), used to checksum bytecode containers
+pub const fn crc32(bytes: &[u8]) -> u32 {
+  let mut crc = 0xFFFFFFFFu32;
+  let mut i = 0;
+  while i < bytes.len() {
+    crc ^= bytes[i] as u32;
+    let mut bit = 0;
+    while bit < 8 {
+      let mask = (crc & 1).wrapping_neg();
+      crc = (crc >> 1) ^ (0xEDB88320 & mask);
+      bit += 1;
+    }
+    i += 1;
+  }
+  !crc
+}
@@ -0,0 +1,136 @@
+use std::fmt::Display;
+
+use crate::{
+  compiler::OPCODE_PARAMS,
+  data_struct::{Records, TYPES},
+  utils::{convert_kind_byte, convert_types_bytes, u32_from_bytes},
+};
+
+/// Opcodes whose param holds a jump target, with the param index
+pub const JUMP_PARAMS: [(u8, usize); 9] = [
+  (2, 2),
+  (4, 2),
+  (5, 0),
+  (9, 0),
+  (17, 0),
+  (19, 2),
+  (20, 2),
+  (21, 2),
+  (22, 2),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProblemKind {
+  /// The buffer ends inside the record starting at `offset`
+  Truncated {
+    offset: usize,
+  },
+  InvalidOpcode(u8),
+  InvalidType {
+    param: usize,
+    bits: usize,
+  },
+  InvalidFlags,
+  JumpOutOfRange {
+    target: u32,
+  },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Problem {
+  pub command: usize,
+  pub kind: ProblemKind,
+}
+impl Display for Problem {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "command {}: ", self.command)?;
+    match self.kind {
+      ProblemKind::Truncated { offset } => {
+        write!(
+          f,
+          "the buffer ends inside the record at byte {offset}"
+        )
+      }
+      ProblemKind::InvalidOpcode(opcode) => {
+        write!(f, "invalid opcode {opcode}")
+      }
+      ProblemKind::InvalidType { param, bits } => {
+        write!(f, "invalid type {bits} in param {param}")
+      }
+      ProblemKind::InvalidFlags => {
+        write!(f, "unknown flag set in the types bytes")
+      }
+      ProblemKind::JumpOutOfRange { target } => {
+        write!(f, "jump target {target} is outside the program")
+      }
+    }
+  }
+}
+
+/// Checks a buffer without running it, so the interpreter never sees
+/// an opcode, type or constant jump it can't handle
+pub fn verify(buffer: &[u8]) -> Result<(), Vec<Problem>> {
+  let mut problems = Vec::new();
+  let mut records = Vec::new();
+  for record in Records::new(buffer) {
+    match record {
+      Ok(record) => records.push(record),
+      Err(offset) => problems.push(Problem {
+        command: records.len(),
+        kind: ProblemKind::Truncated { offset },
+      }),
+    }
+  }
+  let count = records.len();
+
+  for (command, record) in records.iter().enumerate() {
+    let opcode = record.opcode;
+    if opcode == 0 || opcode as usize >= OPCODE_PARAMS.len() {
+      problems.push(Problem {
+        command,
+        kind: ProblemKind::InvalidOpcode(opcode),
+      });
+    }
+
+    let kinds = convert_kind_byte(record.kind_byte);
+    let types = convert_types_bytes(record.types_bytes);
+    for (param, bits) in types.iter().take(3).enumerate() {
+      if *bits >= TYPES.len() {
+        problems.push(Problem {
+          command,
+          kind: ProblemKind::InvalidType {
+            param: param + 1,
+            bits: *bits,
+          },
+        });
+      }
+    }
+    if types[3] > 1 {
+      problems.push(Problem {
+        command,
+        kind: ProblemKind::InvalidFlags,
+      });
+    }
+
+    for (jump, param) in JUMP_PARAMS {
+      if opcode != jump || kinds[param] != 0 {
+        continue;
+      }
+      let value = record.params[param];
+      let target =
+        u32_from_bytes([value[0], value[1], value[2], value[3]]);
+      if target as usize > count {
+        problems.push(Problem {
+          command,
+          kind: ProblemKind::JumpOutOfRange { target },
+        });
+      }
+    }
+  }
+
+  if problems.is_empty() {
+    Ok(())
+  } else {
+    Err(problems)
+  }
+}